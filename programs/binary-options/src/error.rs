@@ -12,7 +12,7 @@ pub enum Errors {
     AmountNotEqualToTotalPayoutAmount,
     #[msg("Deposit amount must be equal to bet_amount.")]
     InvalidDepositAmount,
-    #[msg("Participant must make a prediction and win it.")]
+    #[msg("Participant must predict either Long or Short.")]
     InvalidPrediction,
     #[msg("Winning Amount exceeds deposited amount.")]
     InvalidWinningAmount,
@@ -28,6 +28,8 @@ pub enum Errors {
     WithdrawalDisAllowed,
     #[msg("Invalid participant winner.")]
     InvalidWinner,
+    // no longer returned, accepting a binary option that is not open fails with InvalidStateTransition.
+    // Kept so the error codes that follow keep their numbers.
     #[msg("Create options not initialised or participants limit of two cannot be exceeded.")]
     InvalidParticipantsLimit,
     #[msg("Account is not initialized.")]
@@ -51,6 +53,85 @@ pub enum Errors {
     PythOffline,
     #[msg("The loan value is higher than the collateral value.")]
     LoanValueTooHigh,
+    // no longer returned, kept so the error codes that follow keep their numbers
     #[msg("Program should not try to serialize a price account.")]
     TryToSerializePriceAccount,
+    #[msg("Expiry timestamp must be in the future.")]
+    InvalidExpiry,
+    #[msg("Accept deadline must be in the future and not later than expiry.")]
+    InvalidAcceptDeadline,
+    #[msg("Binary option has not reached its expiry.")]
+    OptionNotExpired,
+    #[msg("Pyth price is not the first price published at or after expiry inside the settlement window.")]
+    SettlementPriceOutsideWindow,
+    #[msg("Price exponent is out of range.")]
+    InvalidPriceExponent,
+    #[msg("Strike price precision must match the price feed exponent.")]
+    PricePrecisionMismatch,
+    #[msg("Pyth price confidence interval is too wide.")]
+    PriceConfidenceTooWide,
+    #[msg("Strike price lies inside the price confidence interval and no later price can settle.")]
+    PriceTooCloseToCall,
+    #[msg("Price feed account is not owned by the configured oracle program.")]
+    InvalidOracleOwner,
+    #[msg("Price feed is not in the registry.")]
+    PriceFeedNotRegistered,
+    #[msg("Price feed is already in the registry.")]
+    PriceFeedAlreadyRegistered,
+    #[msg("Price feed registry is full.")]
+    PriceFeedRegistryFull,
+    #[msg("Price feed does not match the binary option's price feed.")]
+    PriceFeedMismatch,
+    #[msg("Binary option has already been settled.")]
+    AlreadySettled,
+    #[msg("Binary option has not been accepted by a second participant.")]
+    OptionNotAccepted,
+    #[msg("Binary option has not been settled.")]
+    OptionNotSettled,
+    #[msg("Payout has already been claimed.")]
+    AlreadyClaimed,
+    #[msg("Binary option cannot move to the requested state.")]
+    InvalidStateTransition,
+    #[msg("Binary option is no longer open.")]
+    OptionNotOpen,
+    #[msg("Binary option can only be closed once it is claimed, cancelled or expired.")]
+    OptionNotClosable,
+    #[msg("Accept deadline has passed.")]
+    AcceptDeadlinePassed,
+    #[msg("Accept deadline has not passed yet.")]
+    AcceptDeadlineNotReached,
+    #[msg("Void grace period after expiry has not elapsed.")]
+    VoidGracePeriodNotElapsed,
+    #[msg("Token account mint does not match the collateral mint.")]
+    InvalidMint,
+    #[msg("Token account is not owned by the expected participant.")]
+    InvalidTokenAccountOwner,
+    #[msg("Account is not a token account of a supported token program.")]
+    InvalidTokenAccount,
+    #[msg("Token program must be the token program or Token-2022.")]
+    InvalidTokenProgram,
+    #[msg("Amount received by the vault does not match the deposited amount.")]
+    DepositAmountMismatch,
+    #[msg("Collateral mint is not registered.")]
+    CollateralMintNotRegistered,
+    #[msg("Collateral mint is already registered.")]
+    CollateralMintAlreadyRegistered,
+    #[msg("Collateral registry is full.")]
+    CollateralRegistryFull,
+    #[msg("Collateral mint is disabled.")]
+    CollateralMintDisabled,
+    #[msg("Collateral decimals do not match the mint.")]
+    CollateralDecimalsMismatch,
+    #[msg("Stake is outside the limits of the collateral mint.")]
+    StakeOutOfRange,
+    #[msg("Signer is not the pending admin authority.")]
+    NotPendingAdmin,
+    #[msg("There is no pending admin authority.")]
+    NoPendingAdmin,
+    #[msg("Instruction is paused.")]
+    ProtocolPaused,
+    #[msg("Config does not match the one the binary option was created under.")]
+    ConfigMismatch,
+    #[msg("Admin account does not match the one the binary option was created under.")]
+    AdminAccountMismatch,
 }
\ No newline at end of file
@@ -0,0 +1,123 @@
+use anchor_lang::prelude::*;
+
+use crate::state::PauseFlags;
+use crate::state::TooClosePolicy;
+
+// AdminConfig changes, one event per changed parameter
+#[event]
+pub struct OracleProgramUpdated {
+    pub old_oracle_program_id: Pubkey,
+    pub new_oracle_program_id: Pubkey,
+}
+
+#[event]
+pub struct ConfMultiplierUpdated {
+    pub old_conf_multiplier: u8,
+    pub new_conf_multiplier: u8,
+}
+
+#[event]
+pub struct TooClosePolicyUpdated {
+    pub old_too_close_policy: TooClosePolicy,
+    pub new_too_close_policy: TooClosePolicy,
+}
+
+#[event]
+pub struct PushToleranceUpdated {
+    pub old_push_tolerance_bps: u16,
+    pub new_push_tolerance_bps: u16,
+}
+
+#[event]
+pub struct PushFeeUpdated {
+    pub old_push_fee_bps: u16,
+    pub new_push_fee_bps: u16,
+}
+
+#[event]
+pub struct VoidGracePeriodUpdated {
+    pub old_void_grace_period: i64,
+    pub new_void_grace_period: i64,
+}
+
+#[event]
+pub struct KeeperRewardUpdated {
+    pub old_keeper_reward_bps: u16,
+    pub new_keeper_reward_bps: u16,
+}
+
+#[event]
+pub struct GuardianUpdated {
+    pub old_guardian: Pubkey,
+    pub new_guardian: Pubkey,
+}
+
+// Circuit breaker changes, authority is the admin or the guardian
+#[event]
+pub struct PauseFlagsUpdated {
+    pub authority: Pubkey,
+    pub old_pause_flags: PauseFlags,
+    pub new_pause_flags: PauseFlags,
+}
+
+// Admin authority handover
+#[event]
+pub struct AdminProposed {
+    pub admin_auth: Pubkey,
+    pub pending_admin_auth: Pubkey,
+}
+
+#[event]
+pub struct AdminTransferred {
+    pub old_admin_auth: Pubkey,
+    pub new_admin_auth: Pubkey,
+}
+
+#[event]
+pub struct AdminProposalCancelled {
+    pub admin_auth: Pubkey,
+    pub pending_admin_auth: Pubkey,
+}
+
+// Price feed registry changes
+#[event]
+pub struct PriceFeedAdded {
+    pub price_feed_id: Pubkey,
+    pub symbol: String,
+    pub expo: i32,
+    pub staleness_threshold: u64,
+    pub max_conf_bps: u16,
+}
+
+#[event]
+pub struct PriceFeedRemoved {
+    pub price_feed_id: Pubkey,
+}
+
+#[event]
+pub struct PriceFeedUpdated {
+    pub price_feed_id: Pubkey,
+    pub old_expo: i32,
+    pub new_expo: i32,
+    pub old_staleness_threshold: u64,
+    pub new_staleness_threshold: u64,
+    pub old_max_conf_bps: u16,
+    pub new_max_conf_bps: u16,
+}
+
+// Collateral registry changes
+#[event]
+pub struct CollateralMintUpdated {
+    pub mint: Pubkey,
+    pub enabled: bool,
+    pub old_min_stake: u64,
+    pub new_min_stake: u64,
+    pub old_max_stake: u64,
+    pub new_max_stake: u64,
+    pub old_fee_bps: u16,
+    pub new_fee_bps: u16,
+    pub old_min_fee: u64,
+    pub new_min_fee: u64,
+    pub old_max_fee: u64,
+    pub new_max_fee: u64,
+}
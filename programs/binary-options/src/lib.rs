@@ -1,12 +1,27 @@
-use anchor_lang::{prelude::*, system_program};
+use anchor_lang::prelude::*;
 //
 use std::mem::size_of;
 //use anchor_lang::prelude::*;
 //use solana_program::account_info::AccountInfo;
 
 pub mod state;
-use state::PriceFeed;
+pub mod token_interface;
+pub mod events;
+use state::PythPrice;
 use state::AdminConfig;
+use state::AdminConfigParams;
+use state::CreateBinaryOptionParams;
+use state::FixedPrice;
+use state::TooClosePolicy;
+use state::PriceFeedRegistry;
+use state::PriceFeedEntry;
+use state::MAX_PRICE_FEEDS;
+use state::CollateralRegistry;
+use state::CollateralEntry;
+use state::MAX_COLLATERAL_MINTS;
+use state::BPS_DENOMINATOR;
+use state::PauseFlags;
+use events::*;
 
 mod error;
 use error::Errors;
@@ -14,27 +29,122 @@ use error::Errors;
 declare_id!("JCqZPL84bJQKfQ1FZ4cYSWddYNpQCBXPjowjkCcdn9ZB");
 
 const DESCRIPTION_LENGTH: usize = 40; // betting description length
-const STALENESS_THRESHOLD : u64 = 60; // staleness threshold in seconds 60
+const SETTLEMENT_WINDOW : i64 = 30; // settlement price must be published within this many seconds after expiry
+const ACCEPT_DEADLINE_MARGIN : i64 = 60; // offers close at least this many seconds before expiry
 
 #[program]
 pub mod binary_options {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>, config: AdminConfig) -> Result<()> {
+    pub fn initialize(ctx: Context<Initialize>, config: AdminConfigParams) -> Result<()> {
+        config.validate()?;
+
         let deposit_account = &mut ctx.accounts.admin_deposit_account;
         let config_account = &mut ctx.accounts.config;
 
-        config_account.set_inner(config);
+        // the program starts unpaused
+        config_account.set_inner(config.into_config(PauseFlags::default(), *ctx.bumps.get("config").unwrap()));
 
-        deposit_account.admin_auth = *ctx.accounts.admin_auth.key;
+        deposit_account.admin_auth = *ctx.accounts.upgrade_authority.admin_auth.key;
+        deposit_account.bump = *ctx.bumps.get("admin_deposit_account").unwrap();
         deposit_account.admin_auth_bump = *ctx.bumps.get("admin_pda_auth").unwrap();
-        deposit_account.admin_sol_vault_bump = ctx.bumps.get("admin_sol_vault").copied();
         deposit_account.is_initialized = true;
+        deposit_account.pending_admin_auth = None;
+
+        Ok(())
+    }
+
+    // admin changes the oracle, settlement or void parameters, every changed parameter is logged as an event
+    pub fn update_config(ctx: Context<UpdateConfig>, config: AdminConfigParams) -> Result<()> {
+        config.validate()?;
+
+        let config_account = &mut ctx.accounts.config;
+
+        if config_account.oracle_program_id != config.oracle_program_id {
+            emit!(OracleProgramUpdated {
+                old_oracle_program_id: config_account.oracle_program_id,
+                new_oracle_program_id: config.oracle_program_id,
+            });
+        }
+        if config_account.conf_multiplier != config.conf_multiplier {
+            emit!(ConfMultiplierUpdated {
+                old_conf_multiplier: config_account.conf_multiplier,
+                new_conf_multiplier: config.conf_multiplier,
+            });
+        }
+        if config_account.too_close_policy != config.too_close_policy {
+            emit!(TooClosePolicyUpdated {
+                old_too_close_policy: config_account.too_close_policy,
+                new_too_close_policy: config.too_close_policy,
+            });
+        }
+        if config_account.push_tolerance_bps != config.push_tolerance_bps {
+            emit!(PushToleranceUpdated {
+                old_push_tolerance_bps: config_account.push_tolerance_bps,
+                new_push_tolerance_bps: config.push_tolerance_bps,
+            });
+        }
+        if config_account.push_fee_bps != config.push_fee_bps {
+            emit!(PushFeeUpdated {
+                old_push_fee_bps: config_account.push_fee_bps,
+                new_push_fee_bps: config.push_fee_bps,
+            });
+        }
+        if config_account.void_grace_period != config.void_grace_period {
+            emit!(VoidGracePeriodUpdated {
+                old_void_grace_period: config_account.void_grace_period,
+                new_void_grace_period: config.void_grace_period,
+            });
+        }
+        if config_account.keeper_reward_bps != config.keeper_reward_bps {
+            emit!(KeeperRewardUpdated {
+                old_keeper_reward_bps: config_account.keeper_reward_bps,
+                new_keeper_reward_bps: config.keeper_reward_bps,
+            });
+        }
+        if config_account.guardian != config.guardian {
+            emit!(GuardianUpdated {
+                old_guardian: config_account.guardian,
+                new_guardian: config.guardian,
+            });
+        }
+
+        // pause flags are only changed through set_pause_flags
+        let pause_flags = config_account.pause_flags;
+        let bump = config_account.bump;
+        config_account.set_inner(config.into_config(pause_flags, bump));
+
+        Ok(())
+    }
+
+    // admin or guardian halts or resumes instructions, refunds of unmatched offers stay available
+    pub fn set_pause_flags(ctx: Context<SetPauseFlags>, pause_flags: PauseFlags) -> Result<()> {
+        let config_account = &mut ctx.accounts.config;
+
+        emit!(PauseFlagsUpdated {
+            authority: ctx.accounts.authority.key(),
+            old_pause_flags: config_account.pause_flags,
+            new_pause_flags: pause_flags,
+        });
+
+        config_account.pause_flags = pause_flags;
 
         Ok(())
     }
 
-    pub fn create_binary_options(ctx: Context<CreateBinaryOptions>, bet_description: String, bet_amount: u64, strike_price: u64, taker_amount: u64, participantPosition: ParticipantPosition) -> Result<()> {
+    pub fn create_binary_options(ctx: Context<CreateBinaryOptions>, params: CreateBinaryOptionParams) -> Result<()> {
+        let CreateBinaryOptionParams {
+            bet_description,
+            bet_amount,
+            strike_price,
+            taker_amount,
+            participant_position,
+            expiry_ts,
+            accept_deadline,
+            at_strike_rule,
+            price_feed_id,
+        } = params;
+
         if bet_description.trim().is_empty() {
             return Err(Errors::CannotCreateBetting.into());
         }
@@ -56,7 +166,7 @@ pub mod binary_options {
         
         // strike_price
         let valid_amount = {
-            if strike_price > 0 {
+            if strike_price.value > 0 {
                 true
             }
             else{false}
@@ -77,35 +187,124 @@ pub mod binary_options {
         if !valid_amount {
             return Err(Errors::AmountNotgreaterThanZero.into());
         }
+
+        // the maker must take a side, otherwise the winner cannot be told apart at settlement
+        if participant_position == ParticipantPosition::Unknown {
+            return Err(Errors::InvalidPrediction.into());
+        }
+
+        // expiry_ts must be in the future
+        let current_timestamp = Clock::get()?.unix_timestamp;
+        if expiry_ts <= current_timestamp {
+            return Err(Errors::InvalidExpiry.into());
+        }
+
+        // offers close ACCEPT_DEADLINE_MARGIN before expiry at the latest, so no one can take a side
+        // once the outcome is nearly known. accept_deadline defaults to that latest time.
+        let latest_accept_deadline = expiry_ts
+            .checked_sub(ACCEPT_DEADLINE_MARGIN)
+            .ok_or(Errors::Overflow)?;
+        let accept_deadline = accept_deadline.unwrap_or(latest_accept_deadline);
+        if accept_deadline <= current_timestamp || accept_deadline > latest_accept_deadline {
+            return Err(Errors::InvalidAcceptDeadline.into());
+        }
+
+        // the maker picks the price feed from the admin's registry
+        let feed = ctx.accounts.feed_registry
+            .find(&price_feed_id)
+            .ok_or(Errors::PriceFeedNotRegistered)?;
+
+        // the strike must be quoted with the same precision as the price feed it settles against
+        if strike_price.expo != feed.expo {
+            return Err(Errors::PricePrecisionMismatch.into());
+        }
+
+        // the collateral mint must be whitelisted by the admin and enabled
+        let collateral = ctx.accounts.collateral_registry
+            .find(&ctx.accounts.mint.key())
+            .ok_or(Errors::CollateralMintNotRegistered)?;
+        if !collateral.enabled {
+            return Err(Errors::CollateralMintDisabled.into());
+        }
+
+        // both stakes must be within the limits set for the mint
+        let stake_limits = collateral.min_stake..=collateral.max_stake;
+        if !stake_limits.contains(&bet_amount) || !stake_limits.contains(&taker_amount) {
+            return Err(Errors::StakeOutOfRange.into());
+        }
+
+        // the protocol fee is computed up front from the mint's fee schedule
+        let total_stake = bet_amount.checked_add(taker_amount).ok_or(Errors::Overflow)?;
+        let bet_fees = collateral.compute_fee(total_stake)?;
+        // bet_fees must leave a payout for the winner
+        if bet_fees >= total_stake {
+            return Err(Errors::InvalidWinningAmount.into());
+        }
         
         let deposit_account = &mut ctx.accounts.deposit_account;
-        let deposit_auth = &ctx.accounts.deposit_auth;
-        let sys_program = &ctx.accounts.system_program;
+        let deposit_auth = &ctx.accounts.deposit_auth.to_account_info();
+        let token_program = &ctx.accounts.token_program.to_account_info();
+        let system_program = &ctx.accounts.system_program.to_account_info();
+        let mint = &ctx.accounts.mint.to_account_info();
 
         deposit_account.deposit_auth = *ctx.accounts.deposit_auth.key;
         deposit_account.taker_auth = *ctx.accounts.deposit_auth.key;
         deposit_account.auth_bump = *ctx.bumps.get("pda_auth").unwrap();
-        deposit_account.sol_vault_bump = ctx.bumps.get("sol_vault").copied();
+        deposit_account.token_vault_bump = ctx.bumps.get("token_vault").copied();
+        deposit_account.mint = ctx.accounts.mint.key();
+        deposit_account.config = ctx.accounts.config.key();
+        deposit_account.admin_deposit_account = ctx.accounts.admin_deposit_account.key();
         deposit_account.bet_description = bet_description;
         deposit_account.bet_amount = bet_amount;
         deposit_account.strike_price = strike_price;
         deposit_account.taker_amount = taker_amount;
-        deposit_account.first_participant = participantPosition;
-        deposit_account.betting_state = 1;
-
-        let cpi_accounts = system_program::Transfer {
-            from: deposit_auth.to_account_info(),
-            to: ctx.accounts.sol_vault.to_account_info(),
-        };
+        deposit_account.first_participant = participant_position;
+        deposit_account.betting_state = BettingState::Open;
+        deposit_account.expiry_ts = expiry_ts;
+        deposit_account.settle_from_ts = expiry_ts;
+        deposit_account.accept_deadline = accept_deadline;
+        deposit_account.at_strike_rule = at_strike_rule;
+        deposit_account.price_feed_id = price_feed_id;
+        deposit_account.bet_fees = bet_fees;
+        deposit_account.push_tolerance_bps = ctx.accounts.config.push_tolerance_bps;
+        deposit_account.push_fee_bps = ctx.accounts.config.push_fee_bps;
+        deposit_account.void_grace_period = ctx.accounts.config.void_grace_period;
+        deposit_account.outcome = SettlementOutcome::Unsettled;
+
+        // the vault is created under the mint's token program with room for the mint's extensions
+        let pda_auth_key = ctx.accounts.pda_auth.key();
+        let seeds = &[
+            b"token_vault",
+            pda_auth_key.as_ref(),
+            &[deposit_account.token_vault_bump.unwrap()],
+        ];
 
-        let cpi = CpiContext::new(sys_program.to_account_info(), cpi_accounts);
+        let signer = &[&seeds[..]];
 
-        system_program::transfer(cpi, bet_amount)?;
+        token_interface::create_token_account(
+            token_program,
+            system_program,
+            deposit_auth,
+            &ctx.accounts.token_vault.to_account_info(),
+            mint,
+            &ctx.accounts.pda_auth.to_account_info(),
+            signer,
+        )?;
+
+        // the maker pays any transfer fee on top so the vault holds exactly bet_amount
+        token_interface::deposit(
+            token_program,
+            &ctx.accounts.deposit_token_account.to_account_info(),
+            mint,
+            &ctx.accounts.token_vault.to_account_info(),
+            deposit_auth,
+            bet_amount,
+        )?;
 
         Ok(())
     }
 
-    //  accept binary options and deposit native sol
+    //  accept binary options and deposit collateral tokens
     pub fn accept_binary_options(ctx: Context<AcceptBinaryOptions>, amount: u64, participant_position: ParticipantPosition) -> Result<()> {
         let valid_amount = {
             if amount > 0 {
@@ -120,7 +319,12 @@ pub mod binary_options {
         
         let deposit_account = &mut ctx.accounts.deposit_account;
         let deposit_auth = &ctx.accounts.deposit_auth;
-        let sys_program = &ctx.accounts.system_program;
+
+        // offers can only be accepted before their accept deadline
+        let current_timestamp = Clock::get()?.unix_timestamp;
+        if !deposit_account.is_accepting(current_timestamp) {
+            return Err(Errors::AcceptDeadlinePassed.into());
+        }
 
         let valid_amount = {
             if amount == deposit_account.taker_amount {
@@ -138,6 +342,11 @@ pub mod binary_options {
             return Err(Errors::PredictionDisAllowed.into());
         }
 
+        // the taker must take the opposite side explicitly
+        if participant_position == ParticipantPosition::Unknown {
+            return Err(Errors::InvalidPrediction.into());
+        }
+
         let first_participant_position = {
             match deposit_account.first_participant {
                 ParticipantPosition::Long => true,
@@ -168,29 +377,111 @@ pub mod binary_options {
             return Err(Errors::PredictionCannotBeSame.into()); 
         }
 
-        // Lets indicate that prediction has been completed by two participants
-        deposit_account.made_prediction = true;
+        // Lets change the betting state to indicate prediction has been completed by two participants
+        deposit_account.transition(BettingState::Matched)?;
         deposit_account.second_participant = participant_position;
 
         // Lets maintain the pubkey of the second participant
         deposit_account.taker_auth = *ctx.accounts.deposit_auth.key;
-        // Lets change the betting state to indicate limit of two participants has been met
-        deposit_account.betting_state = 2;
 
-        // step 1: deposit sol to participants(limited to two) vault
-        let cpi_accounts = system_program::Transfer {
-            from: deposit_auth.to_account_info(),
-            to: ctx.accounts.sol_vault.to_account_info(),
-        };
+        // step 1: deposit tokens to participants(limited to two) vault, the taker pays any transfer fee on top
+        token_interface::deposit(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.deposit_token_account.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.token_vault.to_account_info(),
+            &deposit_auth.to_account_info(),
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+    // maker cancels a binary option nobody has accepted, the stake is refunded and the account closed
+    pub fn cancel_binary_options(ctx: Context<CancelBinaryOptions>) -> Result<()> {
+        let deposit_account = &mut ctx.accounts.deposit_account;
+        let token_program = &ctx.accounts.token_program.to_account_info();
+        let pda_auth = &ctx.accounts.pda_auth.to_account_info();
+        let token_vault = &ctx.accounts.token_vault.to_account_info();
+        let mint = &ctx.accounts.mint.to_account_info();
+
+        deposit_account.transition(BettingState::Cancelled)?;
+
+        let deposit_account_key = deposit_account.key();
+        let seeds = &[
+            b"auth",
+            deposit_account_key.as_ref(),
+            &[deposit_account.auth_bump],
+        ];
+
+        let signer = &[&seeds[..]];
+
+        // the vault only holds the maker's stake while the option is open, so it is emptied
+        let amount = token_interface::load_token_account(token_vault)?.amount;
+
+        token_interface::transfer(
+            token_program,
+            token_vault,
+            mint,
+            &ctx.accounts.deposit_token_account.to_account_info(),
+            pda_auth,
+            signer,
+            amount,
+        )?;
+
+        // the vault is closed together with the binary option
+        token_interface::close_token_account(
+            token_program,
+            token_vault,
+            mint,
+            &ctx.accounts.deposit_auth.to_account_info(),
+            pda_auth,
+            signer,
+        )?;
+
+        Ok(())
+    }
+
+    // anyone can expire an offer nobody accepted before its accept deadline, the stake is refunded to the maker
+    pub fn expire_binary_options(ctx: Context<ExpireBinaryOptions>) -> Result<()> {
+        let deposit_account = &mut ctx.accounts.deposit_account;
+        let token_program = &ctx.accounts.token_program.to_account_info();
+        let pda_auth = &ctx.accounts.pda_auth.to_account_info();
+        let token_vault = &ctx.accounts.token_vault.to_account_info();
+
+        let current_timestamp = Clock::get()?.unix_timestamp;
+        if deposit_account.is_accepting(current_timestamp) {
+            return Err(Errors::AcceptDeadlineNotReached.into());
+        }
+
+        deposit_account.transition(BettingState::Expired)?;
+
+        // the vault only holds the maker's stake while the option is open, so it is emptied
+        let amount = token_interface::load_token_account(token_vault)?.amount;
+
+        let deposit_account_key = deposit_account.key();
+        let seeds = &[
+            b"auth",
+            deposit_account_key.as_ref(),
+            &[deposit_account.auth_bump],
+        ];
 
-        let cpi = CpiContext::new(sys_program.to_account_info(), cpi_accounts);
+        let signer = &[&seeds[..]];
 
-        system_program::transfer(cpi, amount)?;
+        token_interface::transfer(
+            token_program,
+            token_vault,
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.deposit_token_account.to_account_info(),
+            pda_auth,
+            signer,
+            amount,
+        )?;
 
         Ok(())
     }
 
-    // withdraw native sol 
+    // withdraw collateral tokens
     pub fn withdraw_participant_funds(ctx: Context<WithdrawParticipantFunds>, amount: u64) -> Result<()> {
         let valid_amount = {
             if amount > 0 {
@@ -203,7 +494,7 @@ pub mod binary_options {
             return Err(Errors::AmountNotgreaterThanZero.into());
         }
 
-        let deposit_account = &ctx.accounts.deposit_account;
+        let deposit_account = &mut ctx.accounts.deposit_account;
         let deposit_auth = &ctx.accounts.deposit_auth;
 
         let valid_participant_key = {
@@ -219,26 +510,30 @@ pub mod binary_options {
             return Err(Errors::WithdrawalDisAllowed.into());
         }
 
-        // participant must have made a correct prediction and won it
-        if !deposit_account.made_prediction{
-            return Err(Errors::InvalidPrediction.into());
-        }
-
-        let valid_participant_winner = {
-            // This is a check to determine the person withdrawing is the one who won the prediction
-            if deposit_account.winner_auth.eq(deposit_auth.key) {
-                true
+        let payout = {
+            if deposit_account.outcome.is_refund() {
+                // refunded and pushed options pay each participant back their own stake
+                deposit_account.refund_amount(deposit_account.deposit_auth.eq(deposit_auth.key))?
+            }
+            else {
+                let valid_participant_winner = {
+                    // This is a check to determine the person withdrawing is the one who won the prediction
+                    if deposit_account.winner_auth.eq(deposit_auth.key) {
+                        true
+                    }
+                    else{false}
+                };
+
+                if !valid_participant_winner {
+                    // Invalid participant winner.
+                    return Err(Errors::InvalidWinner.into());
+                }
+                deposit_account.total_payout
             }
-            else{false}
         };
 
-        if !valid_participant_winner {
-            // Invalid participant winner.
-            return Err(Errors::InvalidWinner.into());
-        }
-
         let valid_amount = {
-            if amount == deposit_account.total_payout {
+            if amount == payout {
                 true
             }
             else{false}
@@ -248,69 +543,168 @@ pub mod binary_options {
             return Err(Errors::AmountNotEqualToTotalPayoutAmount.into());
         }
 
-        let sys_program = &ctx.accounts.system_program;
+        // each participant can only claim their payout once
+        let is_first_participant = deposit_account.deposit_auth.eq(deposit_auth.key);
+        let already_claimed = {
+            if is_first_participant {
+                deposit_account.first_participant_claimed
+            }
+            else {
+                deposit_account.second_participant_claimed
+            }
+        };
+        if already_claimed {
+            return Err(Errors::AlreadyClaimed.into());
+        }
+
         //let deposit_account = &ctx.accounts.deposit_account;
-        let pda_auth = &mut ctx.accounts.pda_auth;
-        let sol_vault = &mut ctx.accounts.sol_vault;
+        let deposit_account_key = deposit_account.key();
+        let seeds = &[
+            b"auth",
+            deposit_account_key.as_ref(),
+            &[deposit_account.auth_bump],
+        ];
+
+        let signer = &[&seeds[..]];
 
-        let cpi_accounts = system_program::Transfer {
-            from: sol_vault.to_account_info(),
-            to: ctx.accounts.deposit_auth.to_account_info(),
+        token_interface::transfer(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.token_vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.deposit_token_account.to_account_info(),
+            &ctx.accounts.pda_auth.to_account_info(),
+            signer,
+            amount,
+        )?;
+
+        if is_first_participant {
+            deposit_account.first_participant_claimed = true;
+        }
+        else {
+            deposit_account.second_participant_claimed = true;
+        }
+        deposit_account.claimed_ts = Clock::get()?.unix_timestamp;
+
+        // the option is claimed once every payout owed has been withdrawn
+        let fully_claimed = {
+            if deposit_account.outcome.is_refund() {
+                deposit_account.first_participant_claimed && deposit_account.second_participant_claimed
+            }
+            else {
+                true
+            }
         };
+        if fully_claimed {
+            deposit_account.transition(BettingState::Claimed)?;
+        }
+
+        Ok(())
+    }
 
+    // maker closes a finished binary option, returning the account rent and any vault residue to the maker
+    pub fn close_binary_option(ctx: Context<CloseBinaryOption>) -> Result<()> {
+        let deposit_account = &ctx.accounts.deposit_account;
+        let token_program = &ctx.accounts.token_program.to_account_info();
+        let pda_auth = &ctx.accounts.pda_auth.to_account_info();
+        let token_vault = &ctx.accounts.token_vault.to_account_info();
+        let mint = &ctx.accounts.mint.to_account_info();
+
+        let deposit_account_key = deposit_account.key();
         let seeds = &[
-            b"sol_vault",
-            pda_auth.to_account_info().key.as_ref(),
-            &[deposit_account.sol_vault_bump.unwrap()],
+            b"auth",
+            deposit_account_key.as_ref(),
+            &[deposit_account.auth_bump],
         ];
 
         let signer = &[&seeds[..]];
 
-        let cpi = CpiContext::new_with_signer(sys_program.to_account_info(), cpi_accounts, signer);
+        // every payout has been withdrawn, whatever is left in the vault is dust or unsolicited
+        // deposits and goes back to the maker who funded the vault
+        let amount = token_interface::load_token_account(token_vault)?.amount;
+        if amount > 0 {
+            token_interface::transfer(
+                token_program,
+                token_vault,
+                mint,
+                &ctx.accounts.deposit_token_account.to_account_info(),
+                pda_auth,
+                signer,
+                amount,
+            )?;
+        }
 
-        system_program::transfer(cpi, amount)?;
+        // the vault rent goes back to the maker as well
+        token_interface::close_token_account(
+            token_program,
+            token_vault,
+            mint,
+            &ctx.accounts.deposit_auth.to_account_info(),
+            pda_auth,
+            signer,
+        )?;
 
         Ok(())
     }
 
-    pub fn process_prediction(ctx: Context<ProcessPrediction>, bet_fees: u64) -> Result<()> {
-        let valid_amount = {
-            if bet_fees > 0 {
-                true
-            }
-            else{false}
-        };
-        // amount must be greater than zero
-        if !valid_amount {
-            return Err(Errors::AmountNotgreaterThanZero.into());
-        }
+    // settlement is permissionless: the fee is fixed at creation, every account is bound to the option
+    // and only the first price Pyth published at or after expiry settles, so neither the participants
+    // nor the settler can pick a favourable print.
+    // The settler is paid config.keeper_reward_bps of the fee so keepers settle expired options on time.
+    pub fn process_prediction(ctx: Context<ProcessPrediction>) -> Result<()> {
+        let deposit_account = &mut ctx.accounts.deposit_account;
 
-        // Test Pyth oracle price feeds
-        let price_feed = &ctx.accounts.pyth_price_feed_account;
+        // settlement is only allowed at or after expiry
         let current_timestamp1 = Clock::get()?.unix_timestamp;
-        let current_price = price_feed
-            .get_price_no_older_than(current_timestamp1, STALENESS_THRESHOLD)
-            .ok_or(Errors::PythOffline)?;
+        if current_timestamp1 < deposit_account.expiry_ts {
+            return Err(Errors::OptionNotExpired.into());
+        }
+
+        // the feed limits are taken from the registry, a feed removed by the admin can no longer settle
+        let feed = ctx.accounts.feed_registry
+            .find(&deposit_account.price_feed_id)
+            .ok_or(Errors::PriceFeedNotRegistered)?;
+
+        // the settlement price is the aggregate that replaced the last one published before settle_from_ts,
+        // any later print is rejected even inside the settlement window
+        let current_price = PythPrice::load(&ctx.accounts.pyth_price_feed_account)?;
+        let settlement_window_end = deposit_account.expiry_ts
+            .checked_add(SETTLEMENT_WINDOW)
+            .ok_or(Errors::Overflow)?;
+        if !current_price.is_first_at_or_after(deposit_account.settle_from_ts) || current_price.publish_time > settlement_window_end {
+            return Err(Errors::SettlementPriceOutsideWindow.into());
+        }
+
+        // and it must not be stale
+        let price_age = current_timestamp1.saturating_sub(current_price.publish_time);
+        if price_age > i64::try_from(feed.staleness_threshold).map_err(|_x| error!(Errors::Overflow))? {
+            return Err(Errors::PythOffline.into());
+        }
 
-        let deposit_account = &mut ctx.accounts.deposit_account;
-        let pda_auth = &mut ctx.accounts.pda_auth;
-        let sol_vault = &mut ctx.accounts.sol_vault;
-        let sys_program = &ctx.accounts.system_program;
 
         deposit_account.pyth_price = current_price.price;
         deposit_account.pyth_expo = current_price.expo;
 
-        let base: i32 = 10;
-        let exponent: i32 = deposit_account.pyth_expo;
-        // lets get the actual price based on computation
-        let result = (base as f64).powi(exponent.abs());
-        let result = if exponent < 0 { 1.0 / result } else { result };
-        let actual_price = deposit_account.pyth_price as f64 * result;
-        deposit_account.actual_price = actual_price.trunc() as u64; // We'll remove the decimals for this case
-        let actual_price = deposit_account.actual_price;
-        //
-
+        // normalize the pyth price to the strike's precision using integer math
+        let settlement_price = FixedPrice::from_pyth(current_price.price, current_price.expo)?;
         let strike_price = deposit_account.strike_price;
+        deposit_account.actual_price = settlement_price.normalize(strike_price.expo)?;
+
+        // reject noisy prices whose confidence interval is wider than max_conf_bps of the price
+        state::check_confidence(&settlement_price, current_price.conf, feed.max_conf_bps)?;
+
+        // the market is too close to call when the strike lies inside price ± k·conf
+        let config = &ctx.accounts.config;
+        let too_close_to_call = state::is_too_close_to_call(
+            &settlement_price,
+            current_price.conf,
+            config.conf_multiplier,
+            &strike_price,
+        )?;
+        if too_close_to_call && config.too_close_policy == TooClosePolicy::Wait {
+            // the option stays matched and settles with the first price published after this one
+            deposit_account.skip_settlement_price(current_price.publish_time, settlement_window_end)?;
+            return Ok(());
+        }
 
         let first_participant_position = {
             match deposit_account.first_participant {
@@ -339,275 +733,861 @@ pub mod binary_options {
             // Both predictions cannot not be same.
             return Err(Errors::PredictionCannotBeSame.into());
         }
-        /*
-        let winning_position_bool = {
-            match winning_position {
-                ParticipantPosition::Long => true,
-                ParticipantPosition::Short => false,
-                _ => false
-            }
-        };
-        */
 
-        // We are making an assumption that if the prices match then the long position was correct
-        let winning_position_bool = {
-            if actual_price > 0 && strike_price == actual_price {
-                true
-            }
-            else {false}
-        };
+        // settlement is a one-shot transition, only Matched options can move to Settled or Voided
+        deposit_account.settled_ts = current_timestamp1;
+        deposit_account.settled_by = ctx.accounts.settler.key();
 
-        let bet_amount = deposit_account.bet_amount;
-        let taker_amount = deposit_account.taker_amount;
+        if too_close_to_call {
+            // TooClosePolicy::Refund, the option is voided so both participants get their stake back and no fees are taken
+            return deposit_account.void();
+        }
 
-        let valid_amount = {
-            if bet_amount + taker_amount > bet_fees  {
-                true
+        // the option is a push when there is no winning position
+        let winning_position = state::winning_position(
+            &settlement_price,
+            &strike_price,
+            deposit_account.push_tolerance_bps,
+            deposit_account.at_strike_rule,
+        )?;
+
+        let bet_fees = match winning_position {
+            Some(winning_position) => {
+                let bet_amount = deposit_account.bet_amount;
+                let taker_amount = deposit_account.taker_amount;
+                // bet_fees was fixed from the admin's fee schedule when the option was created
+                let bet_fees = deposit_account.bet_fees;
+
+                // bet_fees cannot exceed (bet_amount + taker_amount)
+                let total_payout = bet_amount
+                    .checked_add(taker_amount)
+                    .ok_or(Errors::Overflow)?
+                    .checked_sub(bet_fees)
+                    .ok_or(Errors::InvalidWinningAmount)?;
+
+                // The winner is whichever participant holds the winning position
+                deposit_account.winner_auth = deposit_account.holder_of(winning_position);
+                deposit_account.total_payout = total_payout;
+                deposit_account.outcome = match winning_position {
+                    ParticipantPosition::Long => SettlementOutcome::LongWins,
+                    _ => SettlementOutcome::ShortWins,
+                };
+                bet_fees
+            }
+            None => {
+                // push, each participant withdraws their own stake less the reduced push fee
+                let push_fees = deposit_account
+                    .push_fee(deposit_account.bet_amount)?
+                    .checked_add(deposit_account.push_fee(deposit_account.taker_amount)?)
+                    .ok_or(Errors::Overflow)?;
+                deposit_account.total_payout = 0;
+                deposit_account.outcome = SettlementOutcome::Push;
+                push_fees
             }
-            else{false}
         };
-        // bet_fees exceeds (bet_amount + taker_amount)
-        if !valid_amount {
-            return Err(Errors::InvalidWinningAmount.into());
+        deposit_account.transition(BettingState::Settled)?;
+
+        // the keeper reward is carved out of the fee, the participants' payouts are unchanged
+        let keeper_reward = ctx.accounts.config.keeper_reward(bet_fees)?;
+        let admin_fees = bet_fees
+            .checked_sub(keeper_reward)
+            .ok_or(Errors::Overflow)?;
+
+        // step 1: deposit the fees less the keeper reward to admin vault
+        let deposit_account_key = deposit_account.key();
+        let seeds = &[
+            b"auth",
+            deposit_account_key.as_ref(),
+            &[deposit_account.auth_bump],
+        ];
+
+        let signer = &[&seeds[..]];
+
+        token_interface::transfer(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.token_vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.admin_token_vault.to_account_info(),
+            &ctx.accounts.pda_auth.to_account_info(),
+            signer,
+            admin_fees,
+        )?;
+
+        // step 2: pay the keeper reward to the settler
+        if keeper_reward > 0 {
+            token_interface::transfer(
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.token_vault.to_account_info(),
+                &ctx.accounts.mint.to_account_info(),
+                &ctx.accounts.settler_token_account.to_account_info(),
+                &ctx.accounts.pda_auth.to_account_info(),
+                signer,
+                keeper_reward,
+            )?;
         }
-        
-        let total_payout: u64 = bet_amount + taker_amount - bet_fees;
-        let mut valid_position = false;
-        // first_participant - deposit_account.deposit_auth
-        // second_participant -  deposit_account.taker_auth
-        /*
-        if first_participant_position && winning_position_bool {
-            deposit_account.winner_auth = deposit_account.deposit_auth;
-            deposit_account.total_payout = total_payout;
-            valid_position = true;
-        }
-        else if second_participant_position && winning_position_bool {
-            deposit_account.winner_auth = deposit_account.taker_auth;
-            deposit_account.total_payout = total_payout;
-            valid_position = true;
-        }
-        else{}
-        */
-
-        // We are making an assumption that if the prices match then the long position was correct (i.e first_participant won)
-        // else second_participant won
-        if first_participant_position && winning_position_bool {
-            deposit_account.winner_auth = deposit_account.deposit_auth;
-            deposit_account.total_payout = total_payout;
-            valid_position = true;
+
+        Ok(())
+    }
+
+    // either participant can void a matched option that was not settled within the grace period after expiry,
+    // both participants then withdraw their own stake without any fee
+    pub fn void_binary_options(ctx: Context<VoidBinaryOptions>) -> Result<()> {
+        let deposit_account = &mut ctx.accounts.deposit_account;
+        let participant = &ctx.accounts.participant;
+
+        if !deposit_account.deposit_auth.eq(participant.key) && !deposit_account.taker_auth.eq(participant.key) {
+            return Err(Errors::Unauthorized.into());
         }
-        else {
-            deposit_account.winner_auth = deposit_account.taker_auth;
-            deposit_account.total_payout = total_payout;
-            valid_position = true;
+
+        // a price published at the end of the settlement window stays usable for the feed's staleness threshold,
+        // the option cannot be voided before then so the losing side cannot void an option that can still settle
+        let staleness_threshold = ctx.accounts.feed_registry
+            .find(&deposit_account.price_feed_id)
+            .map(|feed| feed.staleness_threshold)
+            .unwrap_or(0);
+        let settleable_for = SETTLEMENT_WINDOW
+            .checked_add(i64::try_from(staleness_threshold).map_err(|_x| error!(Errors::Overflow))?)
+            .ok_or(Errors::Overflow)?;
+        let void_after = deposit_account.expiry_ts
+            .checked_add(deposit_account.void_grace_period.max(settleable_for))
+            .ok_or(Errors::Overflow)?;
+        let current_timestamp = Clock::get()?.unix_timestamp;
+        if current_timestamp <= void_after {
+            return Err(Errors::VoidGracePeriodNotElapsed.into());
+        }
+
+        deposit_account.void()
+    }
+
+    // admin adds a price feed that binary options can be created against
+    pub fn add_price_feed(ctx: Context<AddPriceFeed>, feed: PriceFeedEntry) -> Result<()> {
+        feed.validate()?;
+
+        let feed_registry = &mut ctx.accounts.feed_registry;
+        feed_registry.admin_deposit_account = ctx.accounts.admin_deposit_account.key();
+        feed_registry.bump = *ctx.bumps.get("feed_registry").unwrap();
+
+        if feed_registry.find(&feed.price_feed_id).is_some() {
+            return Err(Errors::PriceFeedAlreadyRegistered.into());
+        }
+        if feed_registry.feeds.len() >= MAX_PRICE_FEEDS {
+            return Err(Errors::PriceFeedRegistryFull.into());
         }
 
-        if valid_position {
-            // step 1: deposit (bet_fees) sol to admin vault
-            let cpi_accounts = system_program::Transfer {
-                from: sol_vault.to_account_info(),
-                to: ctx.accounts.admin_sol_vault.to_account_info(),
-            };
+        emit!(PriceFeedAdded {
+            price_feed_id: feed.price_feed_id,
+            symbol: feed.symbol.clone(),
+            expo: feed.expo,
+            staleness_threshold: feed.staleness_threshold,
+            max_conf_bps: feed.max_conf_bps,
+        });
 
+        feed_registry.feeds.push(feed);
+
+        Ok(())
+    }
+
+    // admin changes the exponent, staleness or confidence limits of a registered price feed
+    pub fn update_price_feed(ctx: Context<ManagePriceFeed>, feed: PriceFeedEntry) -> Result<()> {
+        feed.validate()?;
+
+        let entry = ctx.accounts.feed_registry.feeds
+            .iter_mut()
+            .find(|entry| entry.price_feed_id.eq(&feed.price_feed_id))
+            .ok_or(Errors::PriceFeedNotRegistered)?;
+
+        emit!(PriceFeedUpdated {
+            price_feed_id: feed.price_feed_id,
+            old_expo: entry.expo,
+            new_expo: feed.expo,
+            old_staleness_threshold: entry.staleness_threshold,
+            new_staleness_threshold: feed.staleness_threshold,
+            old_max_conf_bps: entry.max_conf_bps,
+            new_max_conf_bps: feed.max_conf_bps,
+        });
+
+        *entry = feed;
+
+        Ok(())
+    }
+
+    // admin removes a price feed, binary options using it can no longer be created or settled
+    pub fn remove_price_feed(ctx: Context<ManagePriceFeed>, price_feed_id: Pubkey) -> Result<()> {
+        let feeds = &mut ctx.accounts.feed_registry.feeds;
+        let index = feeds
+            .iter()
+            .position(|entry| entry.price_feed_id.eq(&price_feed_id))
+            .ok_or(Errors::PriceFeedNotRegistered)?;
+
+        feeds.remove(index);
+
+        emit!(PriceFeedRemoved { price_feed_id });
+
+        Ok(())
+    }
+
+    // admin whitelists a collateral mint and creates the fee vault for it
+    pub fn add_collateral_mint(ctx: Context<AddCollateralMint>, collateral: CollateralEntry) -> Result<()> {
+        collateral.validate()?;
+
+        let mint = &ctx.accounts.mint.to_account_info();
+        if collateral.mint != mint.key() {
+            return Err(Errors::InvalidMint.into());
+        }
+        // stake limits and fees are expressed in base units of the mint
+        if collateral.decimals != token_interface::load_mint(mint)?.decimals {
+            return Err(Errors::CollateralDecimalsMismatch.into());
+        }
+
+        let collateral_registry = &mut ctx.accounts.collateral_registry;
+        collateral_registry.admin_deposit_account = ctx.accounts.admin_deposit_account.key();
+        collateral_registry.bump = *ctx.bumps.get("collateral_registry").unwrap();
+
+        if collateral_registry.find(&collateral.mint).is_some() {
+            return Err(Errors::CollateralMintAlreadyRegistered.into());
+        }
+        if collateral_registry.mints.len() >= MAX_COLLATERAL_MINTS {
+            return Err(Errors::CollateralRegistryFull.into());
+        }
+
+        collateral_registry.mints.push(collateral);
+
+        // fees of binary options in this mint are collected in its fee vault
+        if ctx.accounts.admin_token_vault.data_is_empty() {
+            let admin_pda_auth_key = ctx.accounts.admin_pda_auth.key();
+            let mint_key = mint.key();
             let seeds = &[
-                b"sol_vault",
-                pda_auth.to_account_info().key.as_ref(),
-                &[deposit_account.sol_vault_bump.unwrap()],
+                b"admin_token_vault",
+                admin_pda_auth_key.as_ref(),
+                mint_key.as_ref(),
+                &[*ctx.bumps.get("admin_token_vault").unwrap()],
             ];
 
             let signer = &[&seeds[..]];
 
-            let cpi = CpiContext::new_with_signer(sys_program.to_account_info(), cpi_accounts, signer);
+            token_interface::create_token_account(
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.admin_auth.to_account_info(),
+                &ctx.accounts.admin_token_vault.to_account_info(),
+                mint,
+                &ctx.accounts.admin_pda_auth.to_account_info(),
+                signer,
+            )?;
+        }
 
-            system_program::transfer(cpi, bet_fees)?;
+        Ok(())
+    }
+
+    // admin enables/disables a collateral mint or changes its stake limits and fees
+    pub fn update_collateral_mint(ctx: Context<ManageCollateralMint>, collateral: CollateralEntry) -> Result<()> {
+        collateral.validate()?;
+
+        let entry = ctx.accounts.collateral_registry.mints
+            .iter_mut()
+            .find(|entry| entry.mint.eq(&collateral.mint))
+            .ok_or(Errors::CollateralMintNotRegistered)?;
+
+        // the decimals of a mint never change
+        if collateral.decimals != entry.decimals {
+            return Err(Errors::CollateralDecimalsMismatch.into());
         }
-        
+
+        emit!(CollateralMintUpdated {
+            mint: collateral.mint,
+            enabled: collateral.enabled,
+            old_min_stake: entry.min_stake,
+            new_min_stake: collateral.min_stake,
+            old_max_stake: entry.max_stake,
+            new_max_stake: collateral.max_stake,
+            old_fee_bps: entry.fee_bps,
+            new_fee_bps: collateral.fee_bps,
+            old_min_fee: entry.min_fee,
+            new_min_fee: collateral.min_fee,
+            old_max_fee: entry.max_fee,
+            new_max_fee: collateral.max_fee,
+        });
+
+        *entry = collateral;
+
+        Ok(())
+    }
+
+    // admin proposes a new authority, the handover completes when the new authority accepts
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin_auth: Pubkey) -> Result<()> {
+        let admin_deposit_account = &mut ctx.accounts.admin_deposit_account;
+
+        if new_admin_auth == admin_deposit_account.admin_auth {
+            return Err(Errors::InvalidArgument.into());
+        }
+
+        admin_deposit_account.pending_admin_auth = Some(new_admin_auth);
+
+        emit!(AdminProposed {
+            admin_auth: admin_deposit_account.admin_auth,
+            pending_admin_auth: new_admin_auth,
+        });
+
+        Ok(())
+    }
+
+    // the proposed authority signs to take over as admin
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let admin_deposit_account = &mut ctx.accounts.admin_deposit_account;
+        let old_admin_auth = admin_deposit_account.admin_auth;
+
+        admin_deposit_account.admin_auth = ctx.accounts.pending_admin_auth.key();
+        admin_deposit_account.pending_admin_auth = None;
+
+        emit!(AdminTransferred {
+            old_admin_auth,
+            new_admin_auth: admin_deposit_account.admin_auth,
+        });
+
+        Ok(())
+    }
+
+    // admin withdraws a pending proposal before it is accepted
+    pub fn cancel_admin_proposal(ctx: Context<ProposeAdmin>) -> Result<()> {
+        let admin_deposit_account = &mut ctx.accounts.admin_deposit_account;
+
+        let pending_admin_auth = admin_deposit_account.pending_admin_auth
+            .take()
+            .ok_or(Errors::NoPendingAdmin)?;
+
+        emit!(AdminProposalCancelled {
+            admin_auth: admin_deposit_account.admin_auth,
+            pending_admin_auth,
+        });
+
         Ok(())
     }
 
-    // admin (on behalf of house) withdraws native sol 
+    // admin (on behalf of house) withdraws collected fees of one collateral mint
     pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
-        let sys_program = &ctx.accounts.system_program;
         let deposit_account = &ctx.accounts.admin_deposit_account;
-        let pda_auth = &mut ctx.accounts.admin_pda_auth;
-        let sol_vault = &mut ctx.accounts.admin_sol_vault;
-
-        let cpi_accounts = system_program::Transfer {
-            from: sol_vault.to_account_info(),
-            to: ctx.accounts.admin_auth.to_account_info(),
-        };
 
+        let deposit_account_key = deposit_account.key();
         let seeds = &[
-            b"admin_sol_vault",
-            pda_auth.to_account_info().key.as_ref(),
-            &[deposit_account.admin_sol_vault_bump.unwrap()],
+            b"admin_auth",
+            deposit_account_key.as_ref(),
+            &[deposit_account.admin_auth_bump],
         ];
 
         let signer = &[&seeds[..]];
 
-        let cpi = CpiContext::new_with_signer(sys_program.to_account_info(), cpi_accounts, signer);
-
-        system_program::transfer(cpi, amount)?;
+        token_interface::transfer(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.admin_token_vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.admin_token_account.to_account_info(),
+            &ctx.accounts.admin_pda_auth.to_account_info(),
+            signer,
+            amount,
+        )?;
 
         Ok(())
     }
 
 }
 
+// Signer holding the upgrade authority of the program
+#[derive(Accounts)]
+pub struct UpgradeAuthority<'info> {
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ Errors::Unauthorized)]
+    pub program: Program<'info, crate::program::BinaryOptions>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin_auth.key()) @ Errors::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+    #[account(mut)]
+    pub admin_auth: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct Initialize<'info> {
-    // Pyth Oracle price feeds accounts
-    #[account(address = *program_id @ Errors::Unauthorized)]
-    pub program: Signer<'info>,
+    // only the upgrade authority of the program can initialize it. A composite account runs its constraints
+    // while it is deserialized, so the signer is checked before the config is created.
+    pub upgrade_authority: UpgradeAuthority<'info>,
     //#[account(mut)]
     //pub payer: Signer<'info>,
-    #[account(init, payer = admin_auth, space = 8 + size_of::<AdminConfig>())]
+    #[account(init, payer = upgrade_authority.admin_auth, space = 8 + size_of::<AdminConfig>(), seeds = [b"config"], bump)]
     pub config: Account<'info, AdminConfig>,
     //
-    #[account(init, payer = admin_auth, space = DepositBaseAdmin::LEN,
+    #[account(init, payer = upgrade_authority.admin_auth, space = DepositBaseAdmin::LEN, seeds = [b"admin"], bump,
         constraint = !admin_deposit_account.is_initialized @ Errors::AccountAlreadyInitialized
     )]
     pub admin_deposit_account: Account<'info, DepositBaseAdmin>,
     #[account(seeds = [b"admin_auth", admin_deposit_account.key().as_ref()], bump)]
     /// CHECK: no need to check this.
     pub admin_pda_auth: UncheckedAccount<'info>,
-    #[account(mut, seeds = [b"admin_sol_vault", admin_pda_auth.key().as_ref()], bump)]
-    pub admin_sol_vault: SystemAccount<'info>,
-    #[account(mut)]
-    pub admin_auth: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, AdminConfig>,
+    #[account(seeds = [b"admin"], bump = admin_deposit_account.bump, has_one = admin_auth,
+        constraint = admin_deposit_account.is_initialized @ Errors::AccountNotInitialized
+    )]
+    pub admin_deposit_account: Account<'info, DepositBaseAdmin>,
+    pub admin_auth: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPauseFlags<'info> {
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, AdminConfig>,
+    #[account(seeds = [b"admin"], bump = admin_deposit_account.bump,
+        constraint = admin_deposit_account.is_initialized @ Errors::AccountNotInitialized
+    )]
+    pub admin_deposit_account: Account<'info, DepositBaseAdmin>,
+    #[account(
+        constraint = authority.key() == admin_deposit_account.admin_auth || authority.key() == config.guardian @ Errors::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct CreateBinaryOptions<'info> {
+    #[account(seeds = [b"config"], bump = config.bump,
+        constraint = !config.pause_flags.is_create_paused() @ Errors::ProtocolPaused
+    )]
+    pub config: Account<'info, AdminConfig>,
     #[account(init, payer = deposit_auth, space = BinaryOption::LEN)]
-    pub deposit_account: Account<'info, BinaryOption>,
+    pub deposit_account: Box<Account<'info, BinaryOption>>,
     #[account(seeds = [b"auth", deposit_account.key().as_ref()], bump)]
     /// CHECK: no need to check this.
     pub pda_auth: UncheckedAccount<'info>,
-    #[account(mut, seeds = [b"sol_vault", pda_auth.key().as_ref()], bump)]
-    pub sol_vault: SystemAccount<'info>,
+    // collateral token of the binary option
+    #[account(owner = token_program.key() @ Errors::InvalidMint)]
+    /// CHECK: owned by the token program passed in, parsed by token_interface.
+    pub mint: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"token_vault", pda_auth.key().as_ref()], bump)]
+    /// CHECK: created by token_interface::create_token_account.
+    pub token_vault: UncheckedAccount<'info>,
+    #[account(mut,
+        constraint = token_interface::load_token_account(&deposit_token_account)?.mint == mint.key() @ Errors::InvalidMint,
+        constraint = token_interface::load_token_account(&deposit_token_account)?.owner == deposit_auth.key() @ Errors::InvalidTokenAccountOwner,
+    )]
+    /// CHECK: parsed by token_interface, mint and owner checked by the constraints.
+    pub deposit_token_account: UncheckedAccount<'info>,
     #[account(mut)]
     pub deposit_auth: Signer<'info>,
     //admin accs
-    #[account(mut,
+    #[account(mut, seeds = [b"admin"], bump = admin_deposit_account.bump,
         constraint = admin_deposit_account.is_initialized @ Errors::AccountNotInitialized
     )]
-    pub admin_deposit_account: Account<'info, DepositBaseAdmin>,
+    pub admin_deposit_account: Box<Account<'info, DepositBaseAdmin>>,
+    #[account(seeds = [b"feed_registry", admin_deposit_account.key().as_ref()], bump = feed_registry.bump)]
+    pub feed_registry: Box<Account<'info, PriceFeedRegistry>>,
+    #[account(seeds = [b"collateral_registry", admin_deposit_account.key().as_ref()], bump = collateral_registry.bump)]
+    pub collateral_registry: Box<Account<'info, CollateralRegistry>>,
     //
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ Errors::InvalidTokenProgram)]
+    /// CHECK: classic token program or Token-2022, checked by the constraint.
+    pub token_program: UncheckedAccount<'info>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 pub struct AcceptBinaryOptions<'info> {
+    #[account(seeds = [b"config"], bump = config.bump,
+        constraint = !config.pause_flags.is_accept_paused() @ Errors::ProtocolPaused
+    )]
+    pub config: Account<'info, AdminConfig>,
     //admin accs
-    #[account(mut,
+    #[account(mut, seeds = [b"admin"], bump = admin_deposit_account.bump,
         constraint = admin_deposit_account.is_initialized @ Errors::AccountNotInitialized
     )]
     pub admin_deposit_account: Account<'info, DepositBaseAdmin>,
     #[account(seeds = [b"admin_auth", admin_deposit_account.key().as_ref()], bump = admin_deposit_account.admin_auth_bump)]
     /// CHECK: no need to check this.
     pub admin_pda_auth: UncheckedAccount<'info>,
-    #[account(mut, seeds = [b"admin_sol_vault", admin_pda_auth.key().as_ref()], bump = admin_deposit_account.admin_sol_vault_bump.unwrap())]
-    pub admin_sol_vault: SystemAccount<'info>,
     //admin accs
     #[account(mut,
-        constraint = deposit_account.betting_state == 1 @ Errors::InvalidParticipantsLimit,
+        has_one = config @ Errors::ConfigMismatch,
+        has_one = admin_deposit_account @ Errors::AdminAccountMismatch,
+        constraint = deposit_account.betting_state.can_transition_to(BettingState::Matched) @ Errors::InvalidStateTransition,
     )]
     pub deposit_account: Account<'info, BinaryOption>,
     #[account(seeds = [b"auth", deposit_account.key().as_ref()], bump = deposit_account.auth_bump)]
     /// CHECK: no need to check this.
     pub pda_auth: UncheckedAccount<'info>,
-    #[account(mut, seeds = [b"sol_vault", pda_auth.key().as_ref()], bump = deposit_account.sol_vault_bump.unwrap())]
-    pub sol_vault: SystemAccount<'info>,
+    #[account(address = deposit_account.mint @ Errors::InvalidMint)]
+    /// CHECK: the collateral mint recorded on the binary option.
+    pub mint: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"token_vault", pda_auth.key().as_ref()], bump = deposit_account.token_vault_bump.unwrap())]
+    /// CHECK: the option's token vault, parsed by token_interface.
+    pub token_vault: UncheckedAccount<'info>,
+    #[account(mut,
+        constraint = token_interface::load_token_account(&deposit_token_account)?.mint == deposit_account.mint @ Errors::InvalidMint,
+        constraint = token_interface::load_token_account(&deposit_token_account)?.owner == deposit_auth.key() @ Errors::InvalidTokenAccountOwner,
+    )]
+    /// CHECK: parsed by token_interface, mint and owner checked by the constraints.
+    pub deposit_token_account: UncheckedAccount<'info>,
     #[account(mut)]
     pub deposit_auth: Signer<'info>,
-    pub system_program: Program<'info, System>,
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ Errors::InvalidTokenProgram)]
+    /// CHECK: classic token program or Token-2022, checked by the constraint.
+    pub token_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelBinaryOptions<'info> {
+    #[account(mut, has_one = deposit_auth @ Errors::Unauthorized, close = deposit_auth,
+        constraint = deposit_account.betting_state == BettingState::Open @ Errors::OptionNotOpen,
+    )]
+    pub deposit_account: Account<'info, BinaryOption>,
+    #[account(seeds = [b"auth", deposit_account.key().as_ref()], bump = deposit_account.auth_bump)]
+    /// CHECK: no need to check this.
+    pub pda_auth: UncheckedAccount<'info>,
+    #[account(mut, address = deposit_account.mint @ Errors::InvalidMint)]
+    /// CHECK: the collateral mint recorded on the binary option.
+    pub mint: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"token_vault", pda_auth.key().as_ref()], bump = deposit_account.token_vault_bump.unwrap())]
+    /// CHECK: the option's token vault, parsed by token_interface.
+    pub token_vault: UncheckedAccount<'info>,
+    #[account(mut,
+        constraint = token_interface::load_token_account(&deposit_token_account)?.mint == deposit_account.mint @ Errors::InvalidMint,
+        constraint = token_interface::load_token_account(&deposit_token_account)?.owner == deposit_auth.key() @ Errors::InvalidTokenAccountOwner,
+    )]
+    /// CHECK: parsed by token_interface, mint and owner checked by the constraints.
+    pub deposit_token_account: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub deposit_auth: Signer<'info>,
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ Errors::InvalidTokenProgram)]
+    /// CHECK: classic token program or Token-2022, checked by the constraint.
+    pub token_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireBinaryOptions<'info> {
+    #[account(mut, has_one = deposit_auth @ Errors::Unauthorized,
+        constraint = deposit_account.betting_state == BettingState::Open @ Errors::OptionNotOpen,
+    )]
+    pub deposit_account: Account<'info, BinaryOption>,
+    #[account(seeds = [b"auth", deposit_account.key().as_ref()], bump = deposit_account.auth_bump)]
+    /// CHECK: no need to check this.
+    pub pda_auth: UncheckedAccount<'info>,
+    #[account(address = deposit_account.mint @ Errors::InvalidMint)]
+    /// CHECK: the collateral mint recorded on the binary option.
+    pub mint: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"token_vault", pda_auth.key().as_ref()], bump = deposit_account.token_vault_bump.unwrap())]
+    /// CHECK: the option's token vault, parsed by token_interface.
+    pub token_vault: UncheckedAccount<'info>,
+    #[account(mut,
+        constraint = token_interface::load_token_account(&deposit_token_account)?.mint == deposit_account.mint @ Errors::InvalidMint,
+        constraint = token_interface::load_token_account(&deposit_token_account)?.owner == deposit_auth.key() @ Errors::InvalidTokenAccountOwner,
+    )]
+    /// CHECK: parsed by token_interface, mint and owner checked by the constraints.
+    pub deposit_token_account: UncheckedAccount<'info>,
+    /// CHECK: the maker receiving the refund, checked by has_one on deposit_account.
+    pub deposit_auth: UncheckedAccount<'info>,
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ Errors::InvalidTokenProgram)]
+    /// CHECK: classic token program or Token-2022, checked by the constraint.
+    pub token_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseBinaryOption<'info> {
+    #[account(seeds = [b"config"], bump = config.bump,
+        constraint = !config.pause_flags.is_withdraw_paused() @ Errors::ProtocolPaused
+    )]
+    pub config: Account<'info, AdminConfig>,
+    #[account(mut, has_one = deposit_auth @ Errors::Unauthorized, close = deposit_auth,
+        has_one = config @ Errors::ConfigMismatch,
+        constraint = deposit_account.betting_state.is_closable() @ Errors::OptionNotClosable,
+    )]
+    pub deposit_account: Account<'info, BinaryOption>,
+    #[account(seeds = [b"auth", deposit_account.key().as_ref()], bump = deposit_account.auth_bump)]
+    /// CHECK: no need to check this.
+    pub pda_auth: UncheckedAccount<'info>,
+    #[account(mut, address = deposit_account.mint @ Errors::InvalidMint)]
+    /// CHECK: the collateral mint recorded on the binary option.
+    pub mint: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"token_vault", pda_auth.key().as_ref()], bump = deposit_account.token_vault_bump.unwrap())]
+    /// CHECK: the option's token vault, parsed by token_interface.
+    pub token_vault: UncheckedAccount<'info>,
+    #[account(mut,
+        constraint = token_interface::load_token_account(&deposit_token_account)?.mint == deposit_account.mint @ Errors::InvalidMint,
+        constraint = token_interface::load_token_account(&deposit_token_account)?.owner == deposit_auth.key() @ Errors::InvalidTokenAccountOwner,
+    )]
+    /// CHECK: parsed by token_interface, mint and owner checked by the constraints.
+    pub deposit_token_account: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub deposit_auth: Signer<'info>,
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ Errors::InvalidTokenProgram)]
+    /// CHECK: classic token program or Token-2022, checked by the constraint.
+    pub token_program: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
 pub struct WithdrawParticipantFunds<'info> {
+    #[account(seeds = [b"config"], bump = config.bump,
+        constraint = !config.pause_flags.is_withdraw_paused() @ Errors::ProtocolPaused
+    )]
+    pub config: Account<'info, AdminConfig>,
+    #[account(mut,
+        has_one = config @ Errors::ConfigMismatch,
+        constraint = deposit_account.betting_state != BettingState::Claimed @ Errors::AlreadyClaimed,
+        constraint = deposit_account.betting_state.is_payable() @ Errors::OptionNotSettled,
+    )]
     pub deposit_account: Account<'info, BinaryOption>,
     #[account(seeds = [b"auth", deposit_account.key().as_ref()], bump = deposit_account.auth_bump)]
     /// CHECK: no need to check this.
     pub pda_auth: UncheckedAccount<'info>,
-    #[account(mut, seeds = [b"sol_vault", pda_auth.key().as_ref()], bump = deposit_account.sol_vault_bump.unwrap())]
-    pub sol_vault: SystemAccount<'info>,
+    #[account(address = deposit_account.mint @ Errors::InvalidMint)]
+    /// CHECK: the collateral mint recorded on the binary option.
+    pub mint: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"token_vault", pda_auth.key().as_ref()], bump = deposit_account.token_vault_bump.unwrap())]
+    /// CHECK: the option's token vault, parsed by token_interface.
+    pub token_vault: UncheckedAccount<'info>,
+    #[account(mut,
+        constraint = token_interface::load_token_account(&deposit_token_account)?.mint == deposit_account.mint @ Errors::InvalidMint,
+        constraint = token_interface::load_token_account(&deposit_token_account)?.owner == deposit_auth.key() @ Errors::InvalidTokenAccountOwner,
+    )]
+    /// CHECK: parsed by token_interface, mint and owner checked by the constraints.
+    pub deposit_token_account: UncheckedAccount<'info>,
     #[account(mut)]
     pub deposit_auth: Signer<'info>,
-    pub system_program: Program<'info, System>,
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ Errors::InvalidTokenProgram)]
+    /// CHECK: classic token program or Token-2022, checked by the constraint.
+    pub token_program: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
 pub struct ProcessPrediction<'info> {
     // Pyth Oracle price feeds accounts
+    #[account(seeds = [b"config"], bump = config.bump,
+        constraint = !config.pause_flags.is_settle_paused() @ Errors::ProtocolPaused
+    )]
     pub config: Account<'info, AdminConfig>,
-    #[account(address = config.price_feed_id @ Errors::InvalidArgument)]
-    pub pyth_price_feed_account: Account<'info, PriceFeed>,
+    #[account(address = deposit_account.price_feed_id @ Errors::PriceFeedMismatch,
+        owner = config.oracle_program_id @ Errors::InvalidOracleOwner
+    )]
+    /// CHECK: owner is checked against config.oracle_program_id and the data is parsed by PythPrice::load
+    pub pyth_price_feed_account: UncheckedAccount<'info>,
     //#[account(address = config.collateral_price_feed_id @ Errors::InvalidArgument)]
     //pub pyth_collateral_account: Account<'info, PriceFeed>,
     //
-    #[account(mut)]
+    #[account(mut,
+        has_one = config @ Errors::ConfigMismatch,
+        has_one = admin_deposit_account @ Errors::AdminAccountMismatch,
+        constraint = !deposit_account.betting_state.is_settled() @ Errors::AlreadySettled,
+        constraint = deposit_account.betting_state == BettingState::Matched @ Errors::OptionNotAccepted,
+    )]
     pub deposit_account: Account<'info, BinaryOption>,
     #[account(seeds = [b"auth", deposit_account.key().as_ref()], bump = deposit_account.auth_bump)]
     /// CHECK: no need to check this.
     pub pda_auth: UncheckedAccount<'info>,
-    #[account(mut, seeds = [b"sol_vault", pda_auth.key().as_ref()], bump = deposit_account.sol_vault_bump.unwrap())]
-    pub sol_vault: SystemAccount<'info>,
+    #[account(address = deposit_account.mint @ Errors::InvalidMint)]
+    /// CHECK: the collateral mint recorded on the binary option.
+    pub mint: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"token_vault", pda_auth.key().as_ref()], bump = deposit_account.token_vault_bump.unwrap())]
+    /// CHECK: the option's token vault, parsed by token_interface.
+    pub token_vault: UncheckedAccount<'info>,
     //admin accs
-    #[account(mut,
+    #[account(mut, seeds = [b"admin"], bump = admin_deposit_account.bump,
         constraint = admin_deposit_account.is_initialized @ Errors::AccountNotInitialized
     )]
     pub admin_deposit_account: Account<'info, DepositBaseAdmin>,
     #[account(seeds = [b"admin_auth", admin_deposit_account.key().as_ref()], bump = admin_deposit_account.admin_auth_bump)]
     /// CHECK: no need to check this.
     pub admin_pda_auth: UncheckedAccount<'info>,
-    #[account(mut, seeds = [b"admin_sol_vault", admin_pda_auth.key().as_ref()], bump = admin_deposit_account.admin_sol_vault_bump.unwrap())]
-    pub admin_sol_vault: SystemAccount<'info>,
+    #[account(mut, seeds = [b"admin_token_vault", admin_pda_auth.key().as_ref(), deposit_account.mint.as_ref()], bump)]
+    /// CHECK: the admin fee vault of the collateral mint, parsed by token_interface.
+    pub admin_token_vault: UncheckedAccount<'info>,
+    #[account(seeds = [b"feed_registry", admin_deposit_account.key().as_ref()], bump = feed_registry.bump)]
+    pub feed_registry: Account<'info, PriceFeedRegistry>,
     //admin accs
+    // anyone can settle, the signer is recorded on the binary option and receives the keeper reward
+    pub settler: Signer<'info>,
+    // only checked when the admin pays a keeper reward, otherwise settlers need no token account of the mint
+    #[account(mut,
+        constraint = config.keeper_reward_bps == 0
+            || token_interface::load_token_account(&settler_token_account)?.mint == deposit_account.mint @ Errors::InvalidMint,
+        constraint = config.keeper_reward_bps == 0
+            || token_interface::load_token_account(&settler_token_account)?.owner == settler.key() @ Errors::InvalidTokenAccountOwner,
+    )]
+    /// CHECK: parsed by token_interface when a keeper reward is paid, mint and owner checked by the constraints.
+    pub settler_token_account: UncheckedAccount<'info>,
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ Errors::InvalidTokenProgram)]
+    /// CHECK: classic token program or Token-2022, checked by the constraint.
+    pub token_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct VoidBinaryOptions<'info> {
+    #[account(seeds = [b"config"], bump = config.bump,
+        constraint = !config.pause_flags.is_settle_paused() @ Errors::ProtocolPaused
+    )]
+    pub config: Account<'info, AdminConfig>,
+    #[account(mut,
+        has_one = config @ Errors::ConfigMismatch,
+        constraint = !deposit_account.betting_state.is_settled() @ Errors::AlreadySettled,
+        constraint = deposit_account.betting_state == BettingState::Matched @ Errors::OptionNotAccepted,
+    )]
+    pub deposit_account: Account<'info, BinaryOption>,
+    #[account(seeds = [b"feed_registry", deposit_account.admin_deposit_account.as_ref()], bump = feed_registry.bump)]
+    pub feed_registry: Account<'info, PriceFeedRegistry>,
+    pub participant: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddPriceFeed<'info> {
+    #[account(seeds = [b"admin"], bump = admin_deposit_account.bump, has_one = admin_auth,
+        constraint = admin_deposit_account.is_initialized @ Errors::AccountNotInitialized
+    )]
+    pub admin_deposit_account: Account<'info, DepositBaseAdmin>,
+    #[account(init_if_needed, payer = admin_auth, space = PriceFeedRegistry::LEN,
+        seeds = [b"feed_registry", admin_deposit_account.key().as_ref()], bump
+    )]
+    pub feed_registry: Account<'info, PriceFeedRegistry>,
+    #[account(mut)]
+    pub admin_auth: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Withdraw<'info> {
-    #[account(has_one = admin_auth)]
+pub struct ManagePriceFeed<'info> {
+    #[account(seeds = [b"admin"], bump = admin_deposit_account.bump, has_one = admin_auth)]
     pub admin_deposit_account: Account<'info, DepositBaseAdmin>,
+    #[account(mut, seeds = [b"feed_registry", admin_deposit_account.key().as_ref()], bump = feed_registry.bump)]
+    pub feed_registry: Account<'info, PriceFeedRegistry>,
+    pub admin_auth: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddCollateralMint<'info> {
+    #[account(seeds = [b"admin"], bump = admin_deposit_account.bump, has_one = admin_auth,
+        constraint = admin_deposit_account.is_initialized @ Errors::AccountNotInitialized
+    )]
+    pub admin_deposit_account: Account<'info, DepositBaseAdmin>,
+    #[account(init_if_needed, payer = admin_auth, space = CollateralRegistry::LEN,
+        seeds = [b"collateral_registry", admin_deposit_account.key().as_ref()], bump
+    )]
+    pub collateral_registry: Box<Account<'info, CollateralRegistry>>,
     #[account(seeds = [b"admin_auth", admin_deposit_account.key().as_ref()], bump = admin_deposit_account.admin_auth_bump)]
     /// CHECK: no need to check this.
     pub admin_pda_auth: UncheckedAccount<'info>,
-    #[account(mut, seeds = [b"admin_sol_vault", admin_pda_auth.key().as_ref()], bump = admin_deposit_account.admin_sol_vault_bump.unwrap())]
-    pub admin_sol_vault: SystemAccount<'info>,
+    #[account(owner = token_program.key() @ Errors::InvalidMint)]
+    /// CHECK: owned by the token program passed in, parsed by token_interface.
+    pub mint: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"admin_token_vault", admin_pda_auth.key().as_ref(), mint.key().as_ref()], bump)]
+    /// CHECK: created by token_interface::create_token_account.
+    pub admin_token_vault: UncheckedAccount<'info>,
     #[account(mut)]
     pub admin_auth: Signer<'info>,
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ Errors::InvalidTokenProgram)]
+    /// CHECK: classic token program or Token-2022, checked by the constraint.
+    pub token_program: UncheckedAccount<'info>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct ManageCollateralMint<'info> {
+    #[account(seeds = [b"admin"], bump = admin_deposit_account.bump, has_one = admin_auth)]
+    pub admin_deposit_account: Account<'info, DepositBaseAdmin>,
+    #[account(mut, seeds = [b"collateral_registry", admin_deposit_account.key().as_ref()], bump = collateral_registry.bump)]
+    pub collateral_registry: Account<'info, CollateralRegistry>,
+    pub admin_auth: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    #[account(mut, seeds = [b"admin"], bump = admin_deposit_account.bump, has_one = admin_auth,
+        constraint = admin_deposit_account.is_initialized @ Errors::AccountNotInitialized
+    )]
+    pub admin_deposit_account: Account<'info, DepositBaseAdmin>,
+    pub admin_auth: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(mut, seeds = [b"admin"], bump = admin_deposit_account.bump,
+        constraint = admin_deposit_account.pending_admin_auth == Some(pending_admin_auth.key()) @ Errors::NotPendingAdmin
+    )]
+    pub admin_deposit_account: Account<'info, DepositBaseAdmin>,
+    pub pending_admin_auth: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(seeds = [b"config"], bump = config.bump,
+        constraint = !config.pause_flags.is_withdraw_paused() @ Errors::ProtocolPaused
+    )]
+    pub config: Account<'info, AdminConfig>,
+    #[account(seeds = [b"admin"], bump = admin_deposit_account.bump, has_one = admin_auth)]
+    pub admin_deposit_account: Account<'info, DepositBaseAdmin>,
+    #[account(seeds = [b"admin_auth", admin_deposit_account.key().as_ref()], bump = admin_deposit_account.admin_auth_bump)]
+    /// CHECK: no need to check this.
+    pub admin_pda_auth: UncheckedAccount<'info>,
+    #[account(owner = token_program.key() @ Errors::InvalidMint)]
+    /// CHECK: owned by the token program passed in, parsed by token_interface.
+    pub mint: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"admin_token_vault", admin_pda_auth.key().as_ref(), mint.key().as_ref()], bump)]
+    /// CHECK: the admin fee vault of the mint, parsed by token_interface.
+    pub admin_token_vault: UncheckedAccount<'info>,
+    #[account(mut,
+        constraint = token_interface::load_token_account(&admin_token_account)?.mint == mint.key() @ Errors::InvalidMint,
+    )]
+    /// CHECK: parsed by token_interface, mint checked by the constraint.
+    pub admin_token_account: UncheckedAccount<'info>,
+    pub admin_auth: Signer<'info>,
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ Errors::InvalidTokenProgram)]
+    /// CHECK: classic token program or Token-2022, checked by the constraint.
+    pub token_program: UncheckedAccount<'info>,
+}
+
 #[account]
 pub struct BinaryOption {
     pub deposit_auth: Pubkey,
     pub taker_auth: Pubkey,
     pub winner_auth: Pubkey,
+    // signer of process_prediction
+    pub settled_by: Pubkey,
+    pub price_feed_id: Pubkey,
+    pub mint: Pubkey,
+    // config and admin account the option was created under, later instructions must pass the same ones
+    pub config: Pubkey,
+    pub admin_deposit_account: Pubkey,
     pub auth_bump: u8,
-    pub sol_vault_bump: Option<u8>,
+    pub token_vault_bump: Option<u8>,
     pub bet_description: String,
     pub bet_amount: u64,
     pub taker_amount: u64,
-    pub strike_price: u64,
+    pub strike_price: FixedPrice,
     pub deposited_amount: u64,
-    pub made_prediction: bool,
     pub total_payout: u64,
+    pub bet_fees: u64,
+    pub push_tolerance_bps: u16,
+    pub push_fee_bps: u16,
+    pub void_grace_period: i64,
     pub pyth_price: i64,
     pub pyth_expo: i32,
-    pub actual_price: u64,
+    pub actual_price: FixedPrice,
     pub first_participant: ParticipantPosition,
     pub second_participant: ParticipantPosition,
-    pub betting_state: u8,
+    pub betting_state: BettingState,
+    pub expiry_ts: i64,
+    // the settlement price is the first one published at or after this time, expiry_ts unless
+    // TooClosePolicy::Wait skipped prices too close to call
+    pub settle_from_ts: i64,
+    pub accept_deadline: i64,
+    pub settled_ts: i64,
+    pub claimed_ts: i64,
+    pub first_participant_claimed: bool,
+    pub second_participant_claimed: bool,
+    pub at_strike_rule: AtStrikeRule,
+    pub outcome: SettlementOutcome,
 }
 
 const DISCRIMINATOR_LENGTH: usize = 8;
 const PUBLIC_KEY_LENGTH: usize = 32;
 const U64_LENGTH: usize = 8;
+const I64_LENGTH: usize = 8;
 const U32_LENGTH: usize = 4;
+const U16_LENGTH: usize = 2;
 const U8_LENGTH: usize = 1;
 const BOOL_LENGTH: usize = 1;
 const OPTION_LENGTH: usize = 1; // 1 + (space(T))
@@ -615,32 +1595,122 @@ const ENUM_LENGTH: usize = 1; // 1 + Largest Variant Size
 
 impl BinaryOption {
     const LEN: usize = DISCRIMINATOR_LENGTH +
-                       (PUBLIC_KEY_LENGTH * 3) +
-                       (1 + U8_LENGTH * 3) +
-                       DESCRIPTION_LENGTH +
-                       (U64_LENGTH * 7) +
+                       (PUBLIC_KEY_LENGTH * 8) +
+                       (1 + U8_LENGTH * 2) +
+                       (U32_LENGTH + DESCRIPTION_LENGTH) +
+                       (U64_LENGTH * 6) +
+                       (U16_LENGTH * 2) +
+                       (FixedPrice::LEN * 2) +
                        U32_LENGTH +
-                       BOOL_LENGTH +
                        (ENUM_LENGTH + U8_LENGTH) +
-                       (ENUM_LENGTH + U8_LENGTH);
+                       (ENUM_LENGTH + U8_LENGTH) +
+                       ENUM_LENGTH +
+                       (I64_LENGTH * 6) +
+                       (BOOL_LENGTH * 2) +
+                       ENUM_LENGTH +
+                       ENUM_LENGTH;
+}
+
+impl BinaryOption {
+    // Moves the option to the next betting state, rejecting transitions the state machine does not allow
+    pub fn transition(&mut self, next_state: BettingState) -> Result<()> {
+        if !self.betting_state.can_transition_to(next_state) {
+            return Err(Errors::InvalidStateTransition.into());
+        }
+        self.betting_state = next_state;
+        Ok(())
+    }
+
+    // Participant holding the position, first_participant is deposit_auth and second_participant is taker_auth
+    pub fn holder_of(&self, position: ParticipantPosition) -> Pubkey {
+        if self.first_participant == position {
+            self.deposit_auth
+        }
+        else {
+            self.taker_auth
+        }
+    }
+
+    // The offer can still be accepted, the accept deadline itself is already too late
+    pub fn is_accepting(&self, timestamp: i64) -> bool {
+        timestamp < self.accept_deadline
+    }
+
+    // Voids a matched option, both participants withdraw their own stake and no fees are taken
+    pub fn void(&mut self) -> Result<()> {
+        self.transition(BettingState::Voided)?;
+        self.outcome = SettlementOutcome::Refund;
+        self.total_payout = 0;
+        Ok(())
+    }
+
+    // Skips a settlement price too close to call, the option then settles with the first price published
+    // in a later second. It fails when no such price can fall inside the settlement window.
+    pub fn skip_settlement_price(&mut self, publish_time: i64, settlement_window_end: i64) -> Result<()> {
+        let settle_from_ts = publish_time.checked_add(1).ok_or(Errors::Overflow)?;
+        if settle_from_ts > settlement_window_end {
+            return Err(Errors::PriceTooCloseToCall.into());
+        }
+        self.settle_from_ts = settle_from_ts;
+        Ok(())
+    }
+
+    // Reduced fee a participant pays on their stake when the option is a push. It is capped at the
+    // participant's pro rata share of bet_fees so a push never costs more than a decided option.
+    pub fn push_fee(&self, stake: u64) -> Result<u64> {
+        let fee = (stake as u128)
+            .checked_mul(self.push_fee_bps as u128)
+            .ok_or(Errors::Overflow)?
+            / BPS_DENOMINATOR as u128;
+        let total_stake = (self.bet_amount as u128)
+            .checked_add(self.taker_amount as u128)
+            .ok_or(Errors::Overflow)?;
+        let fee_share = (self.bet_fees as u128)
+            .checked_mul(stake as u128)
+            .ok_or(Errors::Overflow)?
+            .checked_div(total_stake)
+            .ok_or(Errors::Overflow)?;
+        u64::try_from(fee.min(fee_share)).map_err(|_x| error!(Errors::Overflow))
+    }
+
+    // Stake returned to a participant when the option is refunded (no fee) or a push (less the push fee)
+    pub fn refund_amount(&self, is_first_participant: bool) -> Result<u64> {
+        let stake = {
+            if is_first_participant {
+                self.bet_amount
+            }
+            else {
+                self.taker_amount
+            }
+        };
+        if self.outcome == SettlementOutcome::Push {
+            return stake
+                .checked_sub(self.push_fee(stake)?)
+                .ok_or_else(|| error!(Errors::InvalidWinningAmount));
+        }
+        Ok(stake)
+    }
 }
 #[account]
 pub struct DepositBaseAdmin {
     pub admin_auth: Pubkey,
+    pub bump: u8,
     pub admin_auth_bump: u8,
-    pub admin_sol_vault_bump: Option<u8>,
     pub is_initialized: bool,
+    // authority proposed by admin_auth, it becomes admin_auth once it accepts
+    pub pending_admin_auth: Option<Pubkey>,
 }
 
 impl DepositBaseAdmin {
     const LEN: usize = DISCRIMINATOR_LENGTH +
                        PUBLIC_KEY_LENGTH +
-                       (1 + 2 * U8_LENGTH) +
-                       BOOL_LENGTH;
+                       (U8_LENGTH * 2) +
+                       BOOL_LENGTH +
+                       (OPTION_LENGTH + PUBLIC_KEY_LENGTH);
 }
 
 //Calculate the space for the enum. I just gave it value 1
-#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone)]
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
 pub enum ParticipantPosition {
     Long,
     Short,
@@ -651,4 +1721,243 @@ pub enum Participants {
     First,
     Second,
     Unknown,
-}
\ No newline at end of file
+}
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
+pub enum BettingState {
+    // created, waiting for a second participant
+    Open,
+    // accepted by a second participant, waiting for settlement
+    Matched,
+    // settled with a winner, waiting for the payout to be claimed
+    Settled,
+    // every payout owed has been withdrawn
+    Claimed,
+    // withdrawn by the maker before anyone accepted it
+    Cancelled,
+    // accept deadline passed without a second participant
+    Expired,
+    // no winner, both participants withdraw their own stake
+    Voided,
+}
+
+impl BettingState {
+    pub fn can_transition_to(&self, next_state: BettingState) -> bool {
+        matches!(
+            (self, next_state),
+            (BettingState::Open, BettingState::Matched)
+                | (BettingState::Open, BettingState::Cancelled)
+                | (BettingState::Open, BettingState::Expired)
+                | (BettingState::Matched, BettingState::Settled)
+                | (BettingState::Matched, BettingState::Voided)
+                | (BettingState::Settled, BettingState::Claimed)
+                | (BettingState::Voided, BettingState::Claimed)
+        )
+    }
+
+    // settlement has already happened (or the option was voided)
+    pub fn is_settled(&self) -> bool {
+        matches!(self, BettingState::Settled | BettingState::Voided | BettingState::Claimed)
+    }
+
+    // participants can withdraw from the vault
+    pub fn is_payable(&self) -> bool {
+        matches!(self, BettingState::Settled | BettingState::Voided)
+    }
+
+    // nothing is owed to the participants anymore
+    pub fn is_closable(&self) -> bool {
+        matches!(self, BettingState::Claimed | BettingState::Cancelled | BettingState::Expired)
+    }
+}
+
+// Decides the winning position when the settlement price is exactly at the strike
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
+pub enum AtStrikeRule {
+    LongWins,
+    ShortWins,
+    // neither side wins, both participants are refunded less the push fee
+    Push,
+}
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
+pub enum SettlementOutcome {
+    Unsettled,
+    LongWins,
+    ShortWins,
+    Refund,
+    Push,
+}
+
+impl SettlementOutcome {
+    // both participants withdraw their own stake
+    pub fn is_refund(&self) -> bool {
+        matches!(self, SettlementOutcome::Refund | SettlementOutcome::Push)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATES: [BettingState; 7] = [
+        BettingState::Open,
+        BettingState::Matched,
+        BettingState::Settled,
+        BettingState::Claimed,
+        BettingState::Cancelled,
+        BettingState::Expired,
+        BettingState::Voided,
+    ];
+
+    fn binary_option(bet_description: String) -> BinaryOption {
+        BinaryOption {
+            deposit_auth: Pubkey::default(),
+            taker_auth: Pubkey::default(),
+            winner_auth: Pubkey::default(),
+            settled_by: Pubkey::default(),
+            price_feed_id: Pubkey::default(),
+            mint: Pubkey::default(),
+            config: Pubkey::default(),
+            admin_deposit_account: Pubkey::default(),
+            auth_bump: 0,
+            token_vault_bump: Some(0),
+            bet_description,
+            bet_amount: 0,
+            taker_amount: 0,
+            strike_price: FixedPrice::default(),
+            deposited_amount: 0,
+            total_payout: 0,
+            bet_fees: 0,
+            push_tolerance_bps: 0,
+            push_fee_bps: 0,
+            void_grace_period: 0,
+            pyth_price: 0,
+            pyth_expo: 0,
+            actual_price: FixedPrice::default(),
+            first_participant: ParticipantPosition::Long,
+            second_participant: ParticipantPosition::Short,
+            betting_state: BettingState::Open,
+            expiry_ts: 0,
+            settle_from_ts: 0,
+            accept_deadline: 0,
+            settled_ts: 0,
+            claimed_ts: 0,
+            first_participant_claimed: false,
+            second_participant_claimed: false,
+            at_strike_rule: AtStrikeRule::LongWins,
+            outcome: SettlementOutcome::Unsettled,
+        }
+    }
+
+    #[test]
+    fn longest_description_fits_in_len() {
+        let option = binary_option("x".repeat(DESCRIPTION_LENGTH));
+        assert!(DISCRIMINATOR_LENGTH + option.try_to_vec().unwrap().len() <= BinaryOption::LEN);
+    }
+
+    #[test]
+    fn settled_options_cannot_be_reopened_or_resettled() {
+        assert!(!BettingState::Settled.can_transition_to(BettingState::Matched));
+        assert!(!BettingState::Settled.can_transition_to(BettingState::Settled));
+        assert!(!BettingState::Settled.can_transition_to(BettingState::Voided));
+        assert!(!BettingState::Voided.can_transition_to(BettingState::Settled));
+        assert!(!BettingState::Voided.can_transition_to(BettingState::Matched));
+        assert!(!BettingState::Claimed.can_transition_to(BettingState::Claimed));
+    }
+
+    #[test]
+    fn open_options_must_be_matched_before_settlement() {
+        assert!(!BettingState::Open.can_transition_to(BettingState::Settled));
+        assert!(!BettingState::Open.can_transition_to(BettingState::Voided));
+        assert!(!BettingState::Open.can_transition_to(BettingState::Claimed));
+        assert!(!BettingState::Matched.can_transition_to(BettingState::Cancelled));
+        assert!(!BettingState::Matched.can_transition_to(BettingState::Claimed));
+    }
+
+    #[test]
+    fn transition_rejects_invalid_transitions() {
+        let mut option = binary_option(String::from("A:SOL~P:LONG~S:$25~B:2SOL~T:1SOL"));
+        assert_eq!(option.transition(BettingState::Settled).unwrap_err(), error!(Errors::InvalidStateTransition));
+        option.transition(BettingState::Matched).unwrap();
+        option.transition(BettingState::Settled).unwrap();
+        // a settled option can be neither settled again nor voided
+        assert_eq!(option.transition(BettingState::Settled).unwrap_err(), error!(Errors::InvalidStateTransition));
+        assert_eq!(option.transition(BettingState::Voided).unwrap_err(), error!(Errors::InvalidStateTransition));
+        option.transition(BettingState::Claimed).unwrap();
+        assert_eq!(option.transition(BettingState::Claimed).unwrap_err(), error!(Errors::InvalidStateTransition));
+        assert!(option.betting_state == BettingState::Claimed);
+    }
+
+    #[test]
+    fn terminal_states_cannot_move() {
+        for from in [BettingState::Claimed, BettingState::Cancelled, BettingState::Expired] {
+            assert!(STATES.iter().all(|to| !from.can_transition_to(*to)));
+        }
+    }
+
+    #[test]
+    fn skipped_settlement_price_moves_to_the_next_second_inside_the_window() {
+        let mut option = binary_option(String::from("A:SOL~P:LONG~S:$25~B:2SOL~T:1SOL"));
+        option.settle_from_ts = 100;
+        option.skip_settlement_price(100, 130).unwrap();
+        assert_eq!(option.settle_from_ts, 101);
+        option.skip_settlement_price(129, 130).unwrap();
+        assert_eq!(option.settle_from_ts, 130);
+        // no price published after the end of the window can settle the option
+        assert_eq!(option.skip_settlement_price(130, 130).unwrap_err(), error!(Errors::PriceTooCloseToCall));
+        assert_eq!(option.settle_from_ts, 130);
+    }
+
+    #[test]
+    fn offers_cannot_be_accepted_at_or_after_the_deadline() {
+        let mut option = binary_option(String::from("A:SOL~P:LONG~S:$25~B:2SOL~T:1SOL"));
+        option.accept_deadline = 100;
+        assert!(option.is_accepting(99));
+        assert!(!option.is_accepting(100));
+        assert!(!option.is_accepting(101));
+    }
+
+    #[test]
+    fn winner_is_the_participant_holding_the_winning_position() {
+        let mut option = binary_option(String::from("A:SOL~P:LONG~S:$25~B:2SOL~T:1SOL"));
+        option.deposit_auth = Pubkey::new_unique();
+        option.taker_auth = Pubkey::new_unique();
+        option.strike_price = FixedPrice::new(2500, -2).unwrap();
+        let above = FixedPrice::new(2501, -2).unwrap();
+        let below = FixedPrice::new(2499, -2).unwrap();
+        // Long held by the maker, then by the taker
+        let (maker, taker) = (option.deposit_auth, option.taker_auth);
+        for (first, second, long, short) in [
+            (ParticipantPosition::Long, ParticipantPosition::Short, maker, taker),
+            (ParticipantPosition::Short, ParticipantPosition::Long, taker, maker),
+        ] {
+            option.first_participant = first;
+            option.second_participant = second;
+            for (rule, at_strike) in [(AtStrikeRule::LongWins, Some(long)), (AtStrikeRule::ShortWins, Some(short)), (AtStrikeRule::Push, None)] {
+                let winner = |settlement_price: &FixedPrice| state::winning_position(settlement_price, &option.strike_price, 0, rule)
+                    .unwrap()
+                    .map(|position| option.holder_of(position));
+                assert_eq!(winner(&above), Some(long));
+                assert_eq!(winner(&below), Some(short));
+                assert_eq!(winner(&option.strike_price), at_strike);
+            }
+        }
+    }
+
+    #[test]
+    fn voided_options_refund_both_stakes() {
+        let mut option = binary_option(String::from("A:SOL~P:LONG~S:$25~B:2SOL~T:1SOL"));
+        option.bet_amount = 2;
+        option.taker_amount = 1;
+        option.bet_fees = 1;
+        option.push_fee_bps = 10_000;
+        // only matched options can be voided
+        assert_eq!(option.void().unwrap_err(), error!(Errors::InvalidStateTransition));
+        option.transition(BettingState::Matched).unwrap();
+        option.void().unwrap();
+        assert!(option.betting_state == BettingState::Voided);
+        assert!(option.outcome == SettlementOutcome::Refund);
+        assert_eq!(option.refund_amount(true).unwrap(), 2);
+        assert_eq!(option.refund_amount(false).unwrap(), 1);
+        assert_eq!(option.void().unwrap_err(), error!(Errors::InvalidStateTransition));
+    }
+}
@@ -1,49 +1,553 @@
-use std::ops::Deref;
-use std::str::FromStr;
+use std::cmp::Ordering;
 use anchor_lang::prelude::*;
-use pyth_sdk_solana::state::load_price_account;
+use pyth_sdk_solana::state::{load_price_account, PriceStatus};
 
-use crate::Errors;
+use crate::{AtStrikeRule, Errors, ParticipantPosition};
+
+pub const BPS_DENOMINATOR: u64 = 10_000; // basis points
+pub const MAX_KEEPER_REWARD_BPS: u16 = 1_000; // the keeper reward is at most 10% of the protocol fee
+pub const MAX_VOID_GRACE_PERIOD: i64 = 30 * 24 * 60 * 60; // participants can void an unsettled option at most 30 days after expiry
 
 #[account]
 pub struct AdminConfig {
+    // pyth oracle program that must own the price feed accounts (differs per cluster)
+    pub oracle_program_id: Pubkey,
+    // k in price ± k·conf, the band in which the strike is too close to call (0 disables the check)
+    pub conf_multiplier: u8,
+    pub too_close_policy: TooClosePolicy,
+    // settlement prices within push_tolerance_bps of the strike are a push (0 disables the band)
+    pub push_tolerance_bps: u16,
+    // reduced fee each participant pays on their stake when an option is a push, capped at their share of the option fee
+    pub push_fee_bps: u16,
+    // seconds after expiry without a settlement before participants can void the option, copied onto each option at creation
+    pub void_grace_period: i64,
+    // share of the protocol fee paid to whoever settles an option with process_prediction
+    pub keeper_reward_bps: u16,
+    // key that can pause and unpause the program alongside the admin
+    pub guardian: Pubkey,
+    // circuit breaker, only changed through set_pause_flags
+    pub pause_flags: PauseFlags,
+    // bump of the config PDA, set by initialize
+    pub bump: u8,
+}
+
+impl AdminConfig {
+    // Part of the protocol fee paid to the settler, the admin fee vault keeps the rest
+    pub fn keeper_reward(&self, fees: u64) -> Result<u64> {
+        let reward = (fees as u128)
+            .checked_mul(self.keeper_reward_bps as u128)
+            .ok_or(Errors::Overflow)?
+            / BPS_DENOMINATOR as u128;
+        u64::try_from(reward).map_err(|_x| error!(Errors::Overflow))
+    }
+}
+
+// Parameters of initialize and update_config, see AdminConfig.
+// The pause flags and the bump are managed by the program and cannot be passed in.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AdminConfigParams {
+    pub oracle_program_id: Pubkey,
+    pub conf_multiplier: u8,
+    pub too_close_policy: TooClosePolicy,
+    pub push_tolerance_bps: u16,
+    pub push_fee_bps: u16,
+    pub void_grace_period: i64,
+    pub keeper_reward_bps: u16,
+    pub guardian: Pubkey,
+}
+
+impl AdminConfigParams {
+    pub fn validate(&self) -> Result<()> {
+        // push_tolerance_bps and push_fee_bps must be valid basis points values
+        if self.push_tolerance_bps as u64 > BPS_DENOMINATOR || self.push_fee_bps as u64 > BPS_DENOMINATOR {
+            return Err(error!(Errors::InvalidArgument));
+        }
+        // the keeper reward is a small share of the fee, the admin fee vault keeps the rest
+        if self.keeper_reward_bps > MAX_KEEPER_REWARD_BPS {
+            return Err(error!(Errors::InvalidArgument));
+        }
+        // an option cannot be voided while it can still be settled, void_binary_options also waits out
+        // the staleness threshold of the option's price feed after the settlement window
+        if self.void_grace_period < crate::SETTLEMENT_WINDOW || self.void_grace_period > MAX_VOID_GRACE_PERIOD {
+            return Err(error!(Errors::InvalidArgument));
+        }
+        Ok(())
+    }
+
+    pub fn into_config(self, pause_flags: PauseFlags, bump: u8) -> AdminConfig {
+        AdminConfig {
+            oracle_program_id: self.oracle_program_id,
+            conf_multiplier: self.conf_multiplier,
+            too_close_policy: self.too_close_policy,
+            push_tolerance_bps: self.push_tolerance_bps,
+            push_fee_bps: self.push_fee_bps,
+            void_grace_period: self.void_grace_period,
+            keeper_reward_bps: self.keeper_reward_bps,
+            guardian: self.guardian,
+            pause_flags,
+            bump,
+        }
+    }
+}
+
+// Parameters of create_binary_options, see BinaryOption
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CreateBinaryOptionParams {
+    pub bet_description: String,
+    pub bet_amount: u64,
+    pub strike_price: FixedPrice,
+    pub taker_amount: u64,
+    // position taken by the maker
+    pub participant_position: ParticipantPosition,
+    pub expiry_ts: i64,
+    // defaults to the latest deadline allowed before expiry_ts
+    pub accept_deadline: Option<i64>,
+    pub at_strike_rule: AtStrikeRule,
     pub price_feed_id: Pubkey,
 }
 
-#[derive(Clone)]
-pub struct PriceFeed (pyth_sdk::PriceFeed);
+pub const MAX_PRICE_FEEDS: usize = 10; // max number of price feeds in the registry
+pub const FEED_SYMBOL_LENGTH: usize = 16; // price feed symbol length e.g "SOL/USD"
 
-impl anchor_lang::Owner for PriceFeed {
-    fn owner() -> Pubkey {
-        // Make sure the owner is the pyth oracle account on solana devnet
-        let oracle_addr = "gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s";
-        return  Pubkey::from_str(&oracle_addr).unwrap();
+// Price feeds the admin allows binary options to be created against
+#[account]
+pub struct PriceFeedRegistry {
+    pub admin_deposit_account: Pubkey,
+    pub bump: u8,
+    pub feeds: Vec<PriceFeedEntry>,
+}
+
+impl PriceFeedRegistry {
+    pub const LEN: usize = 8 + 32 + 1 + 4 + (MAX_PRICE_FEEDS * PriceFeedEntry::LEN);
+
+    pub fn find(&self, price_feed_id: &Pubkey) -> Option<&PriceFeedEntry> {
+        self.feeds.iter().find(|feed| feed.price_feed_id.eq(price_feed_id))
     }
 }
 
-impl anchor_lang::AccountDeserialize for PriceFeed {
-    fn try_deserialize_unchecked(data: &mut &[u8]) -> Result<Self>{
-        let account = load_price_account(data)
-            .map_err(|_x| error!(Errors::PythError))?;
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PriceFeedEntry {
+    pub symbol: String,
+    pub price_feed_id: Pubkey,
+    // exponent strike prices must be quoted in for this feed
+    pub expo: i32,
+    // staleness threshold in seconds
+    pub staleness_threshold: u64,
+    // maximum Pyth confidence interval as basis points of the price
+    pub max_conf_bps: u16,
+}
+
+impl PriceFeedEntry {
+    pub const LEN: usize = (4 + FEED_SYMBOL_LENGTH) + 32 + 4 + 8 + 2;
+
+    pub fn validate(&self) -> Result<()> {
+        if self.symbol.trim().is_empty() || self.symbol.len() > FEED_SYMBOL_LENGTH {
+            return Err(error!(Errors::InvalidArgument));
+        }
+        if !(FixedPrice::MIN_EXPO..=FixedPrice::MAX_EXPO).contains(&self.expo) {
+            return Err(error!(Errors::InvalidPriceExponent));
+        }
+        if self.staleness_threshold == 0 {
+            return Err(error!(Errors::InvalidArgument));
+        }
+        // max_conf_bps must be a valid basis points value
+        if self.max_conf_bps == 0 || self.max_conf_bps as u64 > BPS_DENOMINATOR {
+            return Err(error!(Errors::InvalidArgument));
+        }
+        Ok(())
+    }
+}
+
+pub const MAX_COLLATERAL_MINTS: usize = 10; // max number of collateral mints in the registry
+
+// Collateral mints the admin allows binary options to be denominated in
+#[account]
+pub struct CollateralRegistry {
+    pub admin_deposit_account: Pubkey,
+    pub bump: u8,
+    pub mints: Vec<CollateralEntry>,
+}
+
+impl CollateralRegistry {
+    pub const LEN: usize = 8 + 32 + 1 + 4 + (MAX_COLLATERAL_MINTS * CollateralEntry::LEN);
+
+    pub fn find(&self, mint: &Pubkey) -> Option<&CollateralEntry> {
+        self.mints.iter().find(|entry| entry.mint.eq(mint))
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CollateralEntry {
+    pub mint: Pubkey,
+    // binary options can only be created with enabled mints
+    pub enabled: bool,
+    // limits on each participant's stake, in base units of the mint
+    pub min_stake: u64,
+    pub max_stake: u64,
+    // protocol fee as basis points of the total stake, clamped to [min_fee, max_fee] in base units of the mint
+    pub fee_bps: u16,
+    pub min_fee: u64,
+    pub max_fee: u64,
+    // decimals of the mint, checked against the mint when it is registered
+    pub decimals: u8,
+}
+
+impl CollateralEntry {
+    pub const LEN: usize = 32 + 1 + 8 + 8 + 2 + 8 + 8 + 1;
+
+    // The push fee needs no check against fee_bps, BinaryOption::push_fee caps it at the option's bet_fees
+    pub fn validate(&self) -> Result<()> {
+        if self.min_stake == 0 || self.min_stake > self.max_stake {
+            return Err(error!(Errors::InvalidArgument));
+        }
+        // fee_bps must be a valid basis points value
+        if self.fee_bps as u64 > BPS_DENOMINATOR || self.min_fee > self.max_fee {
+            return Err(error!(Errors::InvalidArgument));
+        }
+        Ok(())
+    }
+
+    // Protocol fee for a binary option with the given total stake
+    pub fn compute_fee(&self, total_stake: u64) -> Result<u64> {
+        let fee = (total_stake as u128)
+            .checked_mul(self.fee_bps as u128)
+            .ok_or(Errors::Overflow)?
+            / BPS_DENOMINATOR as u128;
+        let fee = u64::try_from(fee).map_err(|_x| error!(Errors::Overflow))?;
+        Ok(fee.clamp(self.min_fee, self.max_fee))
+    }
+}
+
+// Instructions halted by the admin or the guardian, refunds of unmatched offers are never paused
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq, Default)]
+pub struct PauseFlags {
+    // halts every instruction below at once
+    pub all: bool,
+    pub create: bool,
+    pub accept: bool,
+    pub settle: bool,
+    pub withdraw: bool,
+}
+
+impl PauseFlags {
+    pub fn is_create_paused(&self) -> bool {
+        self.all || self.create
+    }
+
+    pub fn is_accept_paused(&self) -> bool {
+        self.all || self.accept
+    }
+
+    pub fn is_settle_paused(&self) -> bool {
+        self.all || self.settle
+    }
+
+    pub fn is_withdraw_paused(&self) -> bool {
+        self.all || self.withdraw
+    }
+}
+
+// What settlement does when the strike lies inside the price confidence band
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
+pub enum TooClosePolicy {
+    // settle with no winner, both participants withdraw their own stake
+    Refund,
+    // skip the price and settle with the first one published after it inside the settlement window,
+    // if none is the option stays matched until a participant voids it
+    Wait,
+}
 
-        // Use a dummy key since the key field will be removed from the SDK
-        let zeros: [u8; 32] = [0; 32];
-        let dummy_key = Pubkey::new(&zeros);
-        let feed = account.to_price_feed(&dummy_key);
-        return Ok(PriceFeed(feed));
+// Fixed-point price: value * 10^expo, e.g. 1850.25 is { value: 185025, expo: -2 }
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FixedPrice {
+    pub value: u64,
+    pub expo: i32,
+}
+
+impl FixedPrice {
+    pub const LEN: usize = 8 + 4;
+    // 10^19 is the largest power of ten that fits in a u64
+    pub const MIN_EXPO: i32 = -19;
+    pub const MAX_EXPO: i32 = 0;
+
+    pub fn new(value: u64, expo: i32) -> Result<Self> {
+        if !(Self::MIN_EXPO..=Self::MAX_EXPO).contains(&expo) {
+            return Err(error!(Errors::InvalidPriceExponent));
+        }
+        Ok(FixedPrice { value, expo })
+    }
+
+    // Pyth prices are signed, a settlement price must be positive
+    pub fn from_pyth(price: i64, expo: i32) -> Result<Self> {
+        if price <= 0 {
+            return Err(error!(Errors::PythError));
+        }
+        Self::new(price as u64, expo)
+    }
+
+    // Rescales the price to target_expo. Moving to a finer exponent is exact,
+    // moving to a coarser exponent truncates the extra decimals.
+    pub fn normalize(&self, target_expo: i32) -> Result<Self> {
+        let target = Self::new(0, target_expo)?;
+        let value = match target.expo.cmp(&self.expo) {
+            Ordering::Equal => self.value,
+            Ordering::Less => self.value
+                .checked_mul(pow10(self.expo - target.expo)?)
+                .ok_or(Errors::Overflow)?,
+            Ordering::Greater => self.value / pow10(target.expo - self.expo)?,
+        };
+        Ok(FixedPrice { value, expo: target.expo })
+    }
+
+    // Exact comparison, both prices are scaled to the finer exponent before comparing
+    pub fn cmp_price(&self, other: &FixedPrice) -> Result<Ordering> {
+        let expo = self.expo.min(other.expo);
+        let lhs = (self.value as u128)
+            .checked_mul(pow10(self.expo - expo)? as u128)
+            .ok_or(Errors::Overflow)?;
+        let rhs = (other.value as u128)
+            .checked_mul(pow10(other.expo - expo)? as u128)
+            .ok_or(Errors::Overflow)?;
+        Ok(lhs.cmp(&rhs))
+    }
+}
+
+fn pow10(exponent: i32) -> Result<u64> {
+    let exponent = u32::try_from(exponent).map_err(|_x| error!(Errors::InvalidPriceExponent))?;
+    10u64.checked_pow(exponent).ok_or_else(|| error!(Errors::Overflow))
+}
+
+// Rejects a noisy price whose confidence interval is wider than max_conf_bps of the price
+pub fn check_confidence(price: &FixedPrice, conf: u64, max_conf_bps: u16) -> Result<()> {
+    let max_conf = (price.value as u128)
+        .checked_mul(max_conf_bps as u128)
+        .ok_or(Errors::Overflow)?
+        / BPS_DENOMINATOR as u128;
+    if conf as u128 > max_conf {
+        return Err(Errors::PriceConfidenceTooWide.into());
     }
+    Ok(())
 }
 
-impl anchor_lang::AccountSerialize for PriceFeed {
-    fn try_serialize<W: std::io::Write>(&self, _writer: &mut W,) -> std::result::Result<(), Error> {
-        Err(error!(Errors::TryToSerializePriceAccount))
+// The market is too close to call when the strike lies inside price ± conf_multiplier·conf, both ends included.
+// A conf_multiplier of 0 disables the check.
+pub fn is_too_close_to_call(price: &FixedPrice, conf: u64, conf_multiplier: u8, strike_price: &FixedPrice) -> Result<bool> {
+    if conf_multiplier == 0 {
+        return Ok(false);
     }
+    let conf_band = conf
+        .checked_mul(conf_multiplier as u64)
+        .ok_or(Errors::Overflow)?;
+    let lower_band = FixedPrice::new(price.value.saturating_sub(conf_band), price.expo)?;
+    let upper_band = FixedPrice::new(
+        price.value.checked_add(conf_band).ok_or(Errors::Overflow)?,
+        price.expo,
+    )?;
+    Ok(lower_band.cmp_price(strike_price)? != Ordering::Greater
+        && upper_band.cmp_price(strike_price)? != Ordering::Less)
 }
 
-impl Deref for PriceFeed {
-    type Target = pyth_sdk::PriceFeed;
+// Long wins if the settlement price is strictly above the strike and Short wins if strictly below.
+// If the settlement price is exactly at the strike, at_strike_rule decides.
+// None means a push, there is no winner. Settlement prices within push_tolerance_bps of the strike are a push.
+pub fn winning_position(
+    settlement_price: &FixedPrice,
+    strike_price: &FixedPrice,
+    push_tolerance_bps: u16,
+    at_strike_rule: AtStrikeRule,
+) -> Result<Option<ParticipantPosition>> {
+    let push_band = (strike_price.value as u128)
+        .checked_mul(push_tolerance_bps as u128)
+        .ok_or(Errors::Overflow)?
+        / BPS_DENOMINATOR as u128;
+    let push_band = u64::try_from(push_band).map_err(|_x| error!(Errors::Overflow))?;
+    let lower_push_band = FixedPrice::new(strike_price.value.saturating_sub(push_band), strike_price.expo)?;
+    let upper_push_band = FixedPrice::new(
+        strike_price.value.checked_add(push_band).ok_or(Errors::Overflow)?,
+        strike_price.expo,
+    )?;
+    let within_push_band = push_tolerance_bps > 0
+        && settlement_price.cmp_price(&lower_push_band)? != Ordering::Less
+        && settlement_price.cmp_price(&upper_push_band)? != Ordering::Greater;
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    Ok(match settlement_price.cmp_price(strike_price)? {
+        _ if within_push_band => None,
+        Ordering::Greater => Some(ParticipantPosition::Long),
+        Ordering::Less => Some(ParticipantPosition::Short),
+        Ordering::Equal => match at_strike_rule {
+            AtStrikeRule::LongWins => Some(ParticipantPosition::Long),
+            AtStrikeRule::ShortWins => Some(ParticipantPosition::Short),
+            AtStrikeRule::Push => None,
+        },
+    })
+}
+
+// Aggregate price of a Pyth price account together with the publish time of the aggregate it replaced
+#[derive(Clone, Copy)]
+pub struct PythPrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+    // publish time of the aggregate this one replaced
+    pub prev_publish_time: i64,
+}
+
+impl PythPrice {
+    // The owner is not checked here, callers constrain the account owner to config.oracle_program_id
+    pub fn load(account: &AccountInfo) -> Result<PythPrice> {
+        let data = account.try_borrow_data()?;
+        let price_account = load_price_account(&data)
+            .map_err(|_x| error!(Errors::PythError))?;
+
+        // only a trading aggregate is a price, otherwise the feed is halted or has too few publishers
+        if price_account.agg.status != PriceStatus::Trading {
+            return Err(error!(Errors::PythOffline));
+        }
+        Ok(PythPrice {
+            price: price_account.agg.price,
+            conf: price_account.agg.conf,
+            expo: price_account.expo,
+            publish_time: price_account.timestamp,
+            prev_publish_time: price_account.prev_timestamp,
+        })
     }
-}
\ No newline at end of file
+
+    // The price is the first one published at or after `timestamp`
+    pub fn is_first_at_or_after(&self, timestamp: i64) -> bool {
+        self.prev_publish_time < timestamp && timestamp <= self.publish_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(value: u64, expo: i32) -> FixedPrice {
+        FixedPrice::new(value, expo).unwrap()
+    }
+
+    #[test]
+    fn new_rejects_exponents_out_of_range() {
+        assert!(FixedPrice::new(1, FixedPrice::MIN_EXPO).is_ok());
+        assert!(FixedPrice::new(1, FixedPrice::MAX_EXPO).is_ok());
+        assert_eq!(FixedPrice::new(1, FixedPrice::MIN_EXPO - 1).unwrap_err(), error!(Errors::InvalidPriceExponent));
+        assert_eq!(FixedPrice::new(1, FixedPrice::MAX_EXPO + 1).unwrap_err(), error!(Errors::InvalidPriceExponent));
+    }
+
+    #[test]
+    fn from_pyth_rejects_non_positive_prices() {
+        assert_eq!(FixedPrice::from_pyth(185025, -2).unwrap(), price(185025, -2));
+        assert_eq!(FixedPrice::from_pyth(0, -2).unwrap_err(), error!(Errors::PythError));
+        assert_eq!(FixedPrice::from_pyth(-1, -2).unwrap_err(), error!(Errors::PythError));
+    }
+
+    #[test]
+    fn normalize_to_finer_exponent_is_exact() {
+        assert_eq!(price(185025, -2).normalize(-2).unwrap(), price(185025, -2));
+        assert_eq!(price(185025, -2).normalize(-4).unwrap(), price(18502500, -4));
+        // 10^19 is the largest power of ten that fits in a u64
+        assert_eq!(price(1, 0).normalize(-19).unwrap(), price(10_000_000_000_000_000_000, -19));
+        assert_eq!(price(2, 0).normalize(-19).unwrap_err(), error!(Errors::Overflow));
+    }
+
+    #[test]
+    fn normalize_to_coarser_exponent_truncates() {
+        assert_eq!(price(185029, -2).normalize(0).unwrap(), price(1850, 0));
+        assert_eq!(price(u64::MAX, -19).normalize(0).unwrap(), price(1, 0));
+        assert_eq!(price(9_999_999_999_999_999_999, -19).normalize(0).unwrap(), price(0, 0));
+    }
+
+    #[test]
+    fn normalize_rejects_target_exponent_out_of_range() {
+        assert_eq!(price(1, 0).normalize(-20).unwrap_err(), error!(Errors::InvalidPriceExponent));
+        assert_eq!(price(1, 0).normalize(1).unwrap_err(), error!(Errors::InvalidPriceExponent));
+    }
+
+    #[test]
+    fn cmp_price_compares_across_exponents() {
+        assert_eq!(price(185025, -2).cmp_price(&price(1850250000, -6)).unwrap(), Ordering::Equal);
+        assert_eq!(price(185025, -2).cmp_price(&price(1850249999, -6)).unwrap(), Ordering::Greater);
+        assert_eq!(price(185025, -2).cmp_price(&price(1850250001, -6)).unwrap(), Ordering::Less);
+        // no precision is lost by truncation
+        assert_eq!(price(1850, 0).cmp_price(&price(185001, -2)).unwrap(), Ordering::Less);
+    }
+
+    #[test]
+    fn only_the_first_price_at_or_after_expiry_settles() {
+        let price = |prev_publish_time, publish_time| PythPrice { price: 1, conf: 0, expo: 0, publish_time, prev_publish_time };
+        assert!(price(99, 100).is_first_at_or_after(100));
+        assert!(price(98, 103).is_first_at_or_after(100));
+        // a later print inside the same second or after it
+        assert!(!price(100, 100).is_first_at_or_after(100));
+        assert!(!price(100, 101).is_first_at_or_after(100));
+        // the last print before expiry
+        assert!(!price(98, 99).is_first_at_or_after(100));
+    }
+
+    #[test]
+    fn confidence_up_to_max_conf_bps_is_accepted() {
+        // 100 bps of 25.00 is 0.25
+        assert!(check_confidence(&price(2500, -2), 25, 100).is_ok());
+        assert_eq!(check_confidence(&price(2500, -2), 26, 100).unwrap_err(), error!(Errors::PriceConfidenceTooWide));
+        assert_eq!(check_confidence(&price(2500, -2), 1, 1).unwrap_err(), error!(Errors::PriceConfidenceTooWide));
+        assert!(check_confidence(&price(2500, -2), 0, 1).is_ok());
+    }
+
+    #[test]
+    fn strike_on_either_end_of_the_conf_band_is_too_close_to_call() {
+        // 25.00 ± 2·0.10, the band is [24.80, 25.20]
+        let settlement = price(2500, -2);
+        assert!(is_too_close_to_call(&settlement, 10, 2, &price(2480, -2)).unwrap());
+        assert!(is_too_close_to_call(&settlement, 10, 2, &price(2520, -2)).unwrap());
+        assert!(is_too_close_to_call(&settlement, 10, 2, &price(2500, -2)).unwrap());
+        assert!(!is_too_close_to_call(&settlement, 10, 2, &price(2479, -2)).unwrap());
+        assert!(!is_too_close_to_call(&settlement, 10, 2, &price(2521, -2)).unwrap());
+        // the strike may be quoted with a coarser exponent than the settlement price
+        assert!(!is_too_close_to_call(&price(2481, -2), 1, 1, &price(25, 0)).unwrap());
+        assert!(is_too_close_to_call(&price(2499, -2), 1, 1, &price(25, 0)).unwrap());
+    }
+
+    #[test]
+    fn zero_conf_multiplier_disables_the_conf_band() {
+        assert!(!is_too_close_to_call(&price(2500, -2), 10, 0, &price(2500, -2)).unwrap());
+        assert!(!is_too_close_to_call(&price(2500, -2), u64::MAX, 0, &price(2500, -2)).unwrap());
+        // the lower end of the band is clamped at zero
+        assert!(is_too_close_to_call(&price(2500, -2), 2500, 1, &price(1, -2)).unwrap());
+        assert_eq!(is_too_close_to_call(&price(2500, -2), u64::MAX, 2, &price(2500, -2)).unwrap_err(), error!(Errors::Overflow));
+    }
+
+    #[test]
+    fn winner_follows_the_side_of_the_strike() {
+        let strike = price(2500, -2);
+        for rule in [AtStrikeRule::LongWins, AtStrikeRule::ShortWins, AtStrikeRule::Push] {
+            // the settlement price may be quoted with a finer exponent than the strike
+            assert!(winning_position(&price(250001, -4), &strike, 0, rule).unwrap() == Some(ParticipantPosition::Long));
+            assert!(winning_position(&price(249999, -4), &strike, 0, rule).unwrap() == Some(ParticipantPosition::Short));
+        }
+        assert!(winning_position(&price(250000, -4), &strike, 0, AtStrikeRule::LongWins).unwrap() == Some(ParticipantPosition::Long));
+        assert!(winning_position(&price(250000, -4), &strike, 0, AtStrikeRule::ShortWins).unwrap() == Some(ParticipantPosition::Short));
+        assert!(winning_position(&price(250000, -4), &strike, 0, AtStrikeRule::Push).unwrap().is_none());
+    }
+
+    #[test]
+    fn prices_within_the_push_band_have_no_winner() {
+        // 100 bps of 25.00 is a band of 0.25 on either side of the strike, both ends included
+        let strike = price(2500, -2);
+        for rule in [AtStrikeRule::LongWins, AtStrikeRule::ShortWins, AtStrikeRule::Push] {
+            assert!(winning_position(&price(2475, -2), &strike, 100, rule).unwrap().is_none());
+            assert!(winning_position(&price(2500, -2), &strike, 100, rule).unwrap().is_none());
+            assert!(winning_position(&price(2525, -2), &strike, 100, rule).unwrap().is_none());
+            assert!(winning_position(&price(2474, -2), &strike, 100, rule).unwrap() == Some(ParticipantPosition::Short));
+            assert!(winning_position(&price(2526, -2), &strike, 100, rule).unwrap() == Some(ParticipantPosition::Long));
+        }
+    }
+
+    #[test]
+    fn cmp_price_handles_exponent_edges() {
+        assert_eq!(price(1, 0).cmp_price(&price(10_000_000_000_000_000_000, -19)).unwrap(), Ordering::Equal);
+        assert_eq!(price(u64::MAX, 0).cmp_price(&price(u64::MAX, -19)).unwrap(), Ordering::Greater);
+        assert_eq!(price(u64::MAX, -19).cmp_price(&price(u64::MAX, 0)).unwrap(), Ordering::Less);
+        assert_eq!(price(0, 0).cmp_price(&price(0, -19)).unwrap(), Ordering::Equal);
+    }
+}
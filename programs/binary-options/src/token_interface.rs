@@ -0,0 +1,234 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::system_program;
+use spl_token_2022::extension::transfer_fee::{TransferFeeAmount, TransferFeeConfig};
+use spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use spl_token_2022::state::{Account, Mint};
+
+use crate::error::Errors;
+
+// Collateral can be held with the classic token program or Token-2022, both share the
+// instruction layout of the base token instructions so every CPI below is built with the
+// spl_token_2022 builders and sent to whichever program owns the mint.
+
+pub fn is_token_program(key: &Pubkey) -> bool {
+    *key == anchor_spl::token::ID || *key == spl_token_2022::ID
+}
+
+// Parses a mint of either token program, including Token-2022 mints with extensions
+pub fn load_mint(account: &AccountInfo) -> Result<Mint> {
+    if !is_token_program(account.owner) {
+        return Err(Errors::InvalidMint.into());
+    }
+    let data = account.try_borrow_data()?;
+    let mint = StateWithExtensions::<Mint>::unpack(&data).map_err(|_x| error!(Errors::InvalidMint))?;
+    Ok(mint.base)
+}
+
+// Parses a token account of either token program, including Token-2022 accounts with extensions
+pub fn load_token_account(account: &AccountInfo) -> Result<Account> {
+    if !is_token_program(account.owner) {
+        return Err(Errors::InvalidTokenAccount.into());
+    }
+    let data = account.try_borrow_data()?;
+    let token_account = StateWithExtensions::<Account>::unpack(&data).map_err(|_x| error!(Errors::InvalidTokenAccount))?;
+    Ok(token_account.base)
+}
+
+// Amount to send and transfer fee charged so that `net_amount` reaches the destination
+pub fn gross_up_transfer_fee(mint: &AccountInfo, net_amount: u64) -> Result<(u64, u64)> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok((net_amount, 0));
+    }
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<Mint>::unpack(&data).map_err(|_x| error!(Errors::InvalidMint))?;
+    match mint.get_extension::<TransferFeeConfig>() {
+        Ok(transfer_fee_config) => {
+            let transfer_fee = transfer_fee_config.get_epoch_fee(Clock::get()?.epoch);
+            let gross_amount = transfer_fee.calculate_pre_fee_amount(net_amount).ok_or(Errors::Overflow)?;
+            let fee = transfer_fee.calculate_fee(gross_amount).ok_or(Errors::Overflow)?;
+            Ok((gross_amount, fee))
+        }
+        Err(_) => Ok((net_amount, 0)),
+    }
+}
+
+// Creates a token account at a program derived address, sized for the extensions the mint requires
+pub fn create_token_account<'info>(
+    token_program: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let space = {
+        let data = mint.try_borrow_data()?;
+        let mint_state = StateWithExtensions::<Mint>::unpack(&data).map_err(|_x| error!(Errors::InvalidMint))?;
+        let mint_extensions = mint_state.get_extension_types()?;
+        ExtensionType::get_account_len::<Account>(&ExtensionType::get_required_init_account_extensions(&mint_extensions))
+    };
+    let lamports = Rent::get()?.minimum_balance(space);
+
+    // anyone can send lamports to the address before it is created, so top it up instead of failing
+    let current_lamports = account.lamports();
+    if current_lamports == 0 {
+        let cpi_accounts = system_program::CreateAccount {
+            from: payer.clone(),
+            to: account.clone(),
+        };
+        let cpi = CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer_seeds);
+        system_program::create_account(cpi, lamports, space as u64, token_program.key)?;
+    }
+    else {
+        let required_lamports = lamports.saturating_sub(current_lamports);
+        if required_lamports > 0 {
+            let cpi_accounts = system_program::Transfer {
+                from: payer.clone(),
+                to: account.clone(),
+            };
+            let cpi = CpiContext::new(system_program.clone(), cpi_accounts);
+            system_program::transfer(cpi, required_lamports)?;
+        }
+        let cpi_accounts = system_program::Allocate {
+            account_to_allocate: account.clone(),
+        };
+        let cpi = CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer_seeds);
+        system_program::allocate(cpi, space as u64)?;
+
+        let cpi_accounts = system_program::Assign {
+            account_to_assign: account.clone(),
+        };
+        let cpi = CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer_seeds);
+        system_program::assign(cpi, token_program.key)?;
+    }
+
+    let ix = spl_token_2022::instruction::initialize_account3(
+        token_program.key,
+        account.key,
+        mint.key,
+        authority.key,
+    )?;
+    invoke_signed(&ix, &[token_program.clone(), account.clone(), mint.clone()], signer_seeds)?;
+
+    Ok(())
+}
+
+// Moves `amount` out of `from`, the destination receives `amount` less any transfer fee
+pub fn transfer<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+) -> Result<()> {
+    let decimals = load_mint(mint)?.decimals;
+    let ix = spl_token_2022::instruction::transfer_checked(
+        token_program.key,
+        from.key,
+        mint.key,
+        to.key,
+        authority.key,
+        &[],
+        amount,
+        decimals,
+    )?;
+    invoke_signed(&ix, &[token_program.clone(), from.clone(), mint.clone(), to.clone(), authority.clone()], signer_seeds)?;
+
+    Ok(())
+}
+
+// Deposits exactly `net_amount` into `vault`, the depositor pays the transfer fee on top.
+// The vault balance is checked afterwards so the recorded stake is what actually landed in the vault.
+pub fn deposit<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    vault: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    net_amount: u64,
+) -> Result<()> {
+    let balance_before = load_token_account(vault)?.amount;
+
+    let decimals = load_mint(mint)?.decimals;
+    let (gross_amount, fee) = gross_up_transfer_fee(mint, net_amount)?;
+    let ix = {
+        if fee > 0 {
+            spl_token_2022::extension::transfer_fee::instruction::transfer_checked_with_fee(
+                token_program.key,
+                from.key,
+                mint.key,
+                vault.key,
+                authority.key,
+                &[],
+                gross_amount,
+                decimals,
+                fee,
+            )?
+        }
+        else {
+            spl_token_2022::instruction::transfer_checked(
+                token_program.key,
+                from.key,
+                mint.key,
+                vault.key,
+                authority.key,
+                &[],
+                net_amount,
+                decimals,
+            )?
+        }
+    };
+    invoke_signed(&ix, &[token_program.clone(), from.clone(), mint.clone(), vault.clone(), authority.clone()], &[])?;
+
+    let balance_after = load_token_account(vault)?.amount;
+    if balance_after.checked_sub(balance_before) != Some(net_amount) {
+        return Err(Errors::DepositAmountMismatch.into());
+    }
+
+    Ok(())
+}
+
+// Closes a token account, Token-2022 refuses to close accounts holding withheld transfer fees
+// so those are harvested to the mint first (harvesting is permissionless)
+pub fn close_token_account<'info>(
+    token_program: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    if *token_program.key == spl_token_2022::ID {
+        let withheld_amount = {
+            let data = account.try_borrow_data()?;
+            let account_state = StateWithExtensions::<Account>::unpack(&data).map_err(|_x| error!(Errors::InvalidTokenAccount))?;
+            match account_state.get_extension::<TransferFeeAmount>() {
+                Ok(transfer_fee_amount) => u64::from(transfer_fee_amount.withheld_amount),
+                Err(_) => 0,
+            }
+        };
+        if withheld_amount > 0 {
+            let ix = spl_token_2022::extension::transfer_fee::instruction::harvest_withheld_tokens_to_mint(
+                token_program.key,
+                mint.key,
+                &[account.key],
+            )?;
+            invoke_signed(&ix, &[token_program.clone(), mint.clone(), account.clone()], &[])?;
+        }
+    }
+
+    let ix = spl_token_2022::instruction::close_account(
+        token_program.key,
+        account.key,
+        destination.key,
+        authority.key,
+        &[],
+    )?;
+    invoke_signed(&ix, &[token_program.clone(), account.clone(), destination.clone(), authority.clone()], signer_seeds)?;
+
+    Ok(())
+}
@@ -1,6 +1,10 @@
 import * as anchor from "@project-serum/anchor";
 import { Program } from "@project-serum/anchor";
 import { BinaryOptions } from "../target/types/binary_options";
+import {
+  createMint, createAccount, mintTo, getAccount, getMint, getMintLen, createInitializeTransferFeeConfigInstruction,
+  createInitializeMintInstruction, ExtensionType, TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID
+} from "@solana/spl-token";
 
 describe("binary-options", () => {
   // Configure the client to use the local cluster.
@@ -8,18 +12,32 @@ describe("binary-options", () => {
   let provider = anchor.AnchorProvider.local("https://api.testnet.solana.com")
 
   const program = anchor.workspace.BinaryOptions as Program<BinaryOptions>;
-  const admin_deposit_account = anchor.web3.Keypair.generate();
-  const admin_auth = anchor.web3.Keypair.generate();
+  const admin_auth = (provider.wallet as anchor.Wallet).payer; // upgrade authority of the deployed program
   const deposit_account = anchor.web3.Keypair.generate();
   const deposit_auth = anchor.web3.Keypair.generate(); // First participant
   const deposit_auth_2 = anchor.web3.Keypair.generate(); // Second participant
-  const config = anchor.web3.Keypair.generate();
+  const guardian = anchor.web3.Keypair.generate(); // can pause the program alongside the admin
+  const cancel_deposit_account = anchor.web3.Keypair.generate(); // binary option the maker cancels
+  const expire_deposit_account = anchor.web3.Keypair.generate(); // binary option nobody accepts before its deadline
+  const fee_deposit_account = anchor.web3.Keypair.generate(); // binary option collateralized with a Token-2022 transfer fee mint
+  const late_deposit_account = anchor.web3.Keypair.generate(); // binary option nobody settles in time, voided by a participant
+  const settler = anchor.web3.Keypair.generate(); // third party settling the binary option, settlement is permissionless
   const fs = require('fs');
   const assert = require("assert");
 
   //let solToUSD = "J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix"; // https://pyth.network/developers/price-feed-ids#solana-devnet
   let solToUSD = "7VJsBtJzgTftYzEeooSDYyjKXvYRWJHdwvbwfBvTg9K"; // https://pyth.network/developers/price-feed-ids#solana-testnet
+  //let pythOracle = "gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s"; // pyth oracle program on solana devnet
+  let pythOracle = "8tfDNiaEyrV6Q1U4DEXrEigs9DoDtkugzFbybENEbCDz"; // pyth oracle program on solana testnet
   var programKey;
+  var expiryTs: number;
+  // collateral token and the participants' token accounts, created in before()
+  var mint: anchor.web3.PublicKey;
+  var deposit_token_account: anchor.web3.PublicKey;
+  var deposit_token_account_2: anchor.web3.PublicKey;
+  var admin_token_account: anchor.web3.PublicKey;
+  var admin_token_vault: anchor.web3.PublicKey;
+  var settler_token_account: anchor.web3.PublicKey;
   try {
       let data = fs.readFileSync(
           './target/deploy/binary_options-keypair.json'
@@ -42,15 +60,33 @@ describe("binary-options", () => {
   console.log("deposit_auth.publicKey - ", deposit_auth.publicKey);
   console.log("deposit_auth_2.publicKey - ", deposit_auth_2.publicKey);
   */
+  // config and admin accounts live at well-known addresses
+  let [config, config_bump] = anchor.web3.PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode("config")],
+    program.programId);
+  let [admin_deposit_account, admin_deposit_bump] = anchor.web3.PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode("admin")],
+    program.programId);
+  let [program_data] = anchor.web3.PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    new anchor.web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111"));
+
   // admin
   let [admin_pda_auth, admin_pda_bump] = anchor.web3.PublicKey.findProgramAddressSync(
     [anchor.utils.bytes.utf8.encode("admin_auth"),
-    admin_deposit_account.publicKey.toBuffer()
+    admin_deposit_account.toBuffer()
     ],
     program.programId);
-  let [admin_sol_vault, admin_sol_bump] = anchor.web3.PublicKey.findProgramAddressSync(
-    [anchor.utils.bytes.utf8.encode("admin_sol_vault"),
-    admin_pda_auth.toBuffer()
+
+  let [feed_registry, feed_registry_bump] = anchor.web3.PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode("feed_registry"),
+    admin_deposit_account.toBuffer()
+    ],
+    program.programId);
+
+  let [collateral_registry, collateral_registry_bump] = anchor.web3.PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode("collateral_registry"),
+    admin_deposit_account.toBuffer()
     ],
     program.programId);
 
@@ -60,12 +96,45 @@ describe("binary-options", () => {
     deposit_account.publicKey.toBuffer()
     ],
     program.programId);
-    let [sol_vault, sol_bump] = anchor.web3.PublicKey.findProgramAddressSync(
-      [anchor.utils.bytes.utf8.encode("sol_vault"),
+    let [token_vault, token_vault_bump] = anchor.web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("token_vault"),
       pda_auth.toBuffer()
       ],
       program.programId);
 
+  let [cancel_pda_auth, cancel_pda_bump] = anchor.web3.PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode("auth"),
+    cancel_deposit_account.publicKey.toBuffer()
+    ],
+    program.programId);
+  let [cancel_token_vault, cancel_token_vault_bump] = anchor.web3.PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode("token_vault"),
+    cancel_pda_auth.toBuffer()
+    ],
+    program.programId);
+
+  let [expire_pda_auth, expire_pda_bump] = anchor.web3.PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode("auth"),
+    expire_deposit_account.publicKey.toBuffer()
+    ],
+    program.programId);
+  let [expire_token_vault, expire_token_vault_bump] = anchor.web3.PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode("token_vault"),
+    expire_pda_auth.toBuffer()
+    ],
+    program.programId);
+
+  let [late_pda_auth, late_pda_bump] = anchor.web3.PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode("auth"),
+    late_deposit_account.publicKey.toBuffer()
+    ],
+    program.programId);
+  let [late_token_vault, late_token_vault_bump] = anchor.web3.PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode("token_vault"),
+    late_pda_auth.toBuffer()
+    ],
+    program.programId);
+
   before(async () => {
 
     let res = await provider.connection.requestAirdrop(admin_auth.publicKey, 100 * anchor.web3.LAMPORTS_PER_SOL);
@@ -108,28 +177,410 @@ describe("binary-options", () => {
 
   });
 
+  // Collateral mint of the given token program that an earlier run against the same deployment registered.
+  // The registry only has room for a few mints, so the suite reuses them instead of registering new ones.
+  async function registeredMint(tokenProgram: anchor.web3.PublicKey) {
+    let registry = await program.account.collateralRegistry.fetchNullable(collateral_registry);
+    for (const entry of registry?.mints ?? []) {
+      let mintInfo = await provider.connection.getAccountInfo(entry.mint);
+      if (mintInfo === null || !mintInfo.owner.equals(tokenProgram)) {
+        continue;
+      }
+      let registeredMintAccount = await getMint(provider.connection, entry.mint, undefined, tokenProgram);
+      if (registeredMintAccount.mintAuthority?.equals(admin_auth.publicKey)) {
+        return entry.mint;
+      }
+    }
+    return null;
+  }
+
+  // Sends createBinaryOptions for a fresh binary option of the maker, for the tests where creation is rejected
+  async function createRejectedBinaryOptions(params) {
+    const rejected_deposit_account = anchor.web3.Keypair.generate();
+    let [rejected_pda_auth] = anchor.web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("auth"),
+      rejected_deposit_account.publicKey.toBuffer()
+      ],
+      program.programId);
+    let [rejected_token_vault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("token_vault"),
+      rejected_pda_auth.toBuffer()
+      ],
+      program.programId);
+
+    return program.methods.createBinaryOptions(params)
+      .accounts({
+        config: config,
+        depositAccount: rejected_deposit_account.publicKey,
+        pdaAuth: rejected_pda_auth,
+        mint: mint,
+        tokenVault: rejected_token_vault,
+        depositTokenAccount: deposit_token_account,
+        depositAuth: deposit_auth.publicKey,
+        adminDepositAccount: admin_deposit_account,
+        feedRegistry: feed_registry,
+        collateralRegistry: collateral_registry,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      }).signers([rejected_deposit_account, deposit_auth]).rpc();
+  }
+
+  // Only the first price Pyth publishes at or after expiry settles an option, so the settlement is sent
+  // as soon as the cluster clock reaches expiry and retried while the program still sees it unexpired
+  async function settleAtExpiry(expiry: number, settle: () => Promise<string>) {
+    let waitMs = expiry * 1000 - Date.now();
+    if (waitMs > 0) {
+      await new Promise((resolve) => setTimeout(resolve, waitMs));
+    }
+    for (;;) {
+      try {
+        return await settle();
+      } catch (error) {
+        if (error.error?.errorCode?.code !== "OptionNotExpired") {
+          throw error;
+        }
+      }
+    }
+  }
+
+  before(async () => {
+    // the collateral token, with the same 9 decimals as SOL
+    mint = await registeredMint(TOKEN_PROGRAM_ID) ?? await createMint(provider.connection, admin_auth, admin_auth.publicKey, null, 9);
+
+    deposit_token_account = await createAccount(provider.connection, deposit_auth, mint, deposit_auth.publicKey);
+    deposit_token_account_2 = await createAccount(provider.connection, deposit_auth_2, mint, deposit_auth_2.publicKey);
+    admin_token_account = await createAccount(provider.connection, admin_auth, mint, admin_auth.publicKey);
+    settler_token_account = await createAccount(provider.connection, admin_auth, mint, settler.publicKey);
+
+    await mintTo(provider.connection, admin_auth, mint, deposit_token_account, admin_auth, 100 * anchor.web3.LAMPORTS_PER_SOL);
+    await mintTo(provider.connection, admin_auth, mint, deposit_token_account_2, admin_auth, 100 * anchor.web3.LAMPORTS_PER_SOL);
+
+    [admin_token_vault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("admin_token_vault"),
+      admin_pda_auth.toBuffer(),
+      mint.toBuffer()
+      ],
+      program.programId);
+  });
+
+  it("Initialize by someone other than the upgrade authority fails", async () => {
+    // the upgrade authority is checked before the config is created, so this also fails with Unauthorized
+    // on a deployment an earlier run already initialized
+    let failed = false;
+    try {
+      await program.methods.initialize(
+        {
+          oracleProgramId: deposit_auth.publicKey,
+          confMultiplier: 0,
+          tooClosePolicy: { refund: {} },
+          pushToleranceBps: 0,
+          pushFeeBps: 0,
+          voidGracePeriod: new anchor.BN(3600),
+          keeperRewardBps: 0,
+          guardian: deposit_auth.publicKey,
+        }
+      )
+        .accounts({
+          upgradeAuthority: {
+            program: program.programId,
+            programData: program_data,
+            adminAuth: deposit_auth.publicKey,
+          },
+          config: config,
+          adminDepositAccount: admin_deposit_account,
+          adminPdaAuth: admin_pda_auth,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        }).signers([deposit_auth]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "Unauthorized");
+    }
+    assert(failed, "initialize should fail when not signed by the upgrade authority");
+  });
+
   it("Is initialized!", async () => {
     // Add your test here.
-    const tx = await program.methods.initialize(
+    const initialConfig = {
+      oracleProgramId: new anchor.web3.PublicKey(pythOracle),
+      confMultiplier: 2, // too close to call when strike lies inside price ± 2·conf
+      tooClosePolicy: { refund: {} },
+      pushToleranceBps: 10, // settlement prices within 0.1% of the strike are a push
+      pushFeeBps: 100, // each participant pays 1% of their stake on a push
+      voidGracePeriod: new anchor.BN(3600), // participants can void an option not settled an hour after expiry
+      keeperRewardBps: 1000, // whoever settles an option receives 10% of its fee
+      guardian: guardian.publicKey,
+    };
+
+    // the config is a singleton, a deployment initialized by an earlier run is reset to the initial parameters
+    const tx = await program.account.adminConfig.fetchNullable(config) === null
+      ? await program.methods.initialize(initialConfig)
+        .accounts({
+          upgradeAuthority: {
+            program: program.programId,
+            programData: program_data,
+            adminAuth: admin_auth.publicKey,
+          },
+          config: config,
+          adminDepositAccount: admin_deposit_account,
+          adminPdaAuth: admin_pda_auth,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        }).signers([admin_auth]).rpc()
+      : await program.methods.updateConfig(initialConfig)
+        .accounts({
+          config: config,
+          adminDepositAccount: admin_deposit_account,
+          adminAuth: admin_auth.publicKey,
+        }).signers([admin_auth]).rpc();
+    console.log("Your transaction signature", tx);
+
+    let result = await program.account.depositBaseAdmin.fetch(admin_deposit_account);
+    console.log(result);
+
+    // the program starts unpaused and the config records its own bump
+    let configResult = await program.account.adminConfig.fetch(config);
+    assert.deepEqual(configResult.pauseFlags, { all: false, create: false, accept: false, settle: false, withdraw: false });
+    assert.equal(configResult.bump, config_bump);
+  });
+  
+  it("Add Price Feed", async () => {
+    const feed = {
+      symbol: "SOL/USD",
+      priceFeedId: new anchor.web3.PublicKey(solToUSD),
+      expo: -8,
+      stalenessThreshold: new anchor.BN(60),
+      maxConfBps: 200, // reject prices whose confidence interval exceeds 2% of the price
+    };
+
+    // the feed stays registered between runs against the same deployment
+    let registry = await program.account.priceFeedRegistry.fetchNullable(feed_registry);
+    let registered = registry !== null && registry.feeds.some((entry) => entry.priceFeedId.equals(feed.priceFeedId));
+    const tx = !registered
+      ? await program.methods.addPriceFeed(feed)
+        .accounts({
+          adminDepositAccount: admin_deposit_account,
+          feedRegistry: feed_registry,
+          adminAuth: admin_auth.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        }).signers([admin_auth]).rpc()
+      : await program.methods.updatePriceFeed(feed)
+        .accounts({
+          adminDepositAccount: admin_deposit_account,
+          feedRegistry: feed_registry,
+          adminAuth: admin_auth.publicKey,
+        }).signers([admin_auth]).rpc();
+    console.log("Your transaction signature", tx);
+
+    let result = await program.account.priceFeedRegistry.fetch(feed_registry);
+    console.log("addPriceFeed: ", result);
+  });
+
+  it("Add Collateral Mint", async () => {
+    const collateral = {
+      mint: mint,
+      enabled: true,
+      minStake: new anchor.BN(0.1 * anchor.web3.LAMPORTS_PER_SOL),
+      maxStake: new anchor.BN(50 * anchor.web3.LAMPORTS_PER_SOL),
+      feeBps: 500, // 5% of the total stake
+      minFee: new anchor.BN(0.01 * anchor.web3.LAMPORTS_PER_SOL),
+      maxFee: new anchor.BN(3 * anchor.web3.LAMPORTS_PER_SOL),
+      decimals: 9,
+    };
+
+    // a mint reused from an earlier run is already registered, its limits and fees are reset instead
+    let registry = await program.account.collateralRegistry.fetchNullable(collateral_registry);
+    let registered = registry !== null && registry.mints.some((entry) => entry.mint.equals(mint));
+    if (registered) {
+      const tx = await program.methods.updateCollateralMint(collateral)
+        .accounts({
+          adminDepositAccount: admin_deposit_account,
+          collateralRegistry: collateral_registry,
+          adminAuth: admin_auth.publicKey,
+        }).signers([admin_auth]).rpc();
+      console.log("Your transaction signature", tx);
+      return;
+    }
+
+    const tx = await program.methods.addCollateralMint(collateral)
+      .accounts({
+        adminDepositAccount: admin_deposit_account,
+        collateralRegistry: collateral_registry,
+        adminPdaAuth: admin_pda_auth,
+        mint: mint,
+        adminTokenVault: admin_token_vault,
+        adminAuth: admin_auth.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      }).signers([admin_auth]).rpc();
+    console.log("Your transaction signature", tx);
+
+    let result = await program.account.collateralRegistry.fetch(collateral_registry);
+    console.log("addCollateralMint: ", result);
+    assert.equal((await getAccount(provider.connection, admin_token_vault)).amount, BigInt(0));
+  });
+
+  it("Update Config", async () => {
+    let listener = null;
+    let event = new Promise((resolve) => {
+      listener = program.addEventListener("VoidGracePeriodUpdated", (event) => resolve(event));
+    });
+
+    const tx = await program.methods.updateConfig(
       {
-        priceFeedId: new anchor.web3.PublicKey(solToUSD)
+        oracleProgramId: new anchor.web3.PublicKey(pythOracle),
+        confMultiplier: 2,
+        tooClosePolicy: { refund: {} },
+        pushToleranceBps: 10,
+        pushFeeBps: 100,
+        voidGracePeriod: new anchor.BN(7200), // participants can void an option not settled two hours after expiry
+        keeperRewardBps: 1000,
+        guardian: guardian.publicKey,
       }
     )
       .accounts({
-        program: program.programId,
-        config: config.publicKey,
-        adminDepositAccount: admin_deposit_account.publicKey,
-        adminPdaAuth: admin_pda_auth,
-        adminSolVault: admin_sol_vault,
+        config: config,
+        adminDepositAccount: admin_deposit_account,
         adminAuth: admin_auth.publicKey,
-        systemProgram: anchor.web3.SystemProgram.programId,
-      }).signers([programKey, config, admin_deposit_account, admin_auth]).rpc();
+      }).signers([admin_auth]).rpc();
     console.log("Your transaction signature", tx);
 
-    let result = await program.account.depositBaseAdmin.fetch(admin_deposit_account.publicKey);
-    console.log(result);
+    let result = await program.account.adminConfig.fetch(config);
+    assert.equal(result.voidGracePeriod.toNumber(), 7200);
+
+    // only the changed parameter is logged
+    let updated: any = await event;
+    assert.equal(updated.oldVoidGracePeriod.toNumber(), 3600);
+    assert.equal(updated.newVoidGracePeriod.toNumber(), 7200);
+    await program.removeEventListener(listener);
   });
-  
+
+  it("Update Config by a non-admin fails", async () => {
+    let failed = false;
+    try {
+      await program.methods.updateConfig(
+        {
+          oracleProgramId: deposit_auth.publicKey,
+          confMultiplier: 0,
+          tooClosePolicy: { refund: {} },
+          pushToleranceBps: 0,
+          pushFeeBps: 0,
+          voidGracePeriod: new anchor.BN(3600),
+          keeperRewardBps: 0,
+          guardian: deposit_auth.publicKey,
+        }
+      )
+        .accounts({
+          config: config,
+          adminDepositAccount: admin_deposit_account,
+          adminAuth: deposit_auth.publicKey,
+        }).signers([deposit_auth]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "ConstraintHasOne");
+    }
+    assert(failed, "updateConfig should fail when not signed by the admin");
+  });
+
+  it("Update Config with a keeper reward above the cap fails", async () => {
+    let failed = false;
+    try {
+      await program.methods.updateConfig(
+        {
+          oracleProgramId: new anchor.web3.PublicKey(pythOracle),
+          confMultiplier: 2,
+          tooClosePolicy: { refund: {} },
+          pushToleranceBps: 10,
+          pushFeeBps: 100,
+          voidGracePeriod: new anchor.BN(7200),
+          keeperRewardBps: 1001, // at most 10% of the fee
+          guardian: guardian.publicKey,
+        }
+      )
+        .accounts({
+          config: config,
+          adminDepositAccount: admin_deposit_account,
+          adminAuth: admin_auth.publicKey,
+        }).signers([admin_auth]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "InvalidArgument");
+    }
+    assert(failed, "updateConfig should fail when the keeper reward exceeds its cap");
+  });
+
+  it("Update Config with a void grace period above the cap fails", async () => {
+    let failed = false;
+    try {
+      await program.methods.updateConfig(
+        {
+          oracleProgramId: new anchor.web3.PublicKey(pythOracle),
+          confMultiplier: 2,
+          tooClosePolicy: { refund: {} },
+          pushToleranceBps: 10,
+          pushFeeBps: 100,
+          voidGracePeriod: new anchor.BN(30 * 24 * 60 * 60 + 1), // at most 30 days
+          keeperRewardBps: 1000,
+          guardian: guardian.publicKey,
+        }
+      )
+        .accounts({
+          config: config,
+          adminDepositAccount: admin_deposit_account,
+          adminAuth: admin_auth.publicKey,
+        }).signers([admin_auth]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "InvalidArgument");
+    }
+    assert(failed, "updateConfig should fail when the void grace period exceeds its cap");
+  });
+
+  it("Create Binary Options with a stake above the collateral limit fails", async () => {
+    let failed = false;
+    try {
+      await createRejectedBinaryOptions({ betDescription: 'A:SOL~P:LONG~S:$25~B:60SOL~T:1SOL', betAmount: new anchor.BN(60 * anchor.web3.LAMPORTS_PER_SOL), strikePrice: { value: new anchor.BN(25 * 10 ** 8), expo: -8 }, takerAmount: new anchor.BN(1 * anchor.web3.LAMPORTS_PER_SOL), participantPosition: { long: {} }, expiryTs: new anchor.BN(Math.floor(Date.now() / 1000) + 600), acceptDeadline: null, atStrikeRule: { longWins: {} }, priceFeedId: new anchor.web3.PublicKey(solToUSD) });
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "StakeOutOfRange");
+    }
+    assert(failed, "createBinaryOptions should fail when the stake exceeds the collateral limit");
+  });
+
+  it("Create Binary Options without a position fails", async () => {
+    let failed = false;
+    try {
+      await createRejectedBinaryOptions({ betDescription: 'A:SOL~P:?~S:$25~B:10SOL~T:5SOL', betAmount: new anchor.BN(10 * anchor.web3.LAMPORTS_PER_SOL), strikePrice: { value: new anchor.BN(25 * 10 ** 8), expo: -8 }, takerAmount: new anchor.BN(5 * anchor.web3.LAMPORTS_PER_SOL), participantPosition: { unknown: {} }, expiryTs: new anchor.BN(Math.floor(Date.now() / 1000) + 600), acceptDeadline: null, atStrikeRule: { longWins: {} }, priceFeedId: new anchor.web3.PublicKey(solToUSD) });
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "InvalidPrediction");
+    }
+    assert(failed, "createBinaryOptions should fail when the maker does not pick Long or Short");
+  });
+
+  it("Create Binary Options with a strike precision other than the price feed's fails", async () => {
+    let failed = false;
+    try {
+      // the feed is registered with expo -8
+      await createRejectedBinaryOptions({ betDescription: 'A:SOL~P:LONG~S:$25~B:10SOL~T:5SOL', betAmount: new anchor.BN(10 * anchor.web3.LAMPORTS_PER_SOL), strikePrice: { value: new anchor.BN(25 * 10 ** 6), expo: -6 }, takerAmount: new anchor.BN(5 * anchor.web3.LAMPORTS_PER_SOL), participantPosition: { long: {} }, expiryTs: new anchor.BN(Math.floor(Date.now() / 1000) + 600), acceptDeadline: null, atStrikeRule: { longWins: {} }, priceFeedId: new anchor.web3.PublicKey(solToUSD) });
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "PricePrecisionMismatch");
+    }
+    assert(failed, "createBinaryOptions should fail when the strike is not quoted in the feed's exponent");
+  });
+
+  it("Create Binary Options with an accept deadline too close to expiry fails", async () => {
+    let failed = false;
+    try {
+      // offers must close at least 60 seconds before expiry
+      let rejectedExpiryTs = Math.floor(Date.now() / 1000) + 600;
+      await createRejectedBinaryOptions({ betDescription: 'A:SOL~P:LONG~S:$25~B:10SOL~T:5SOL', betAmount: new anchor.BN(10 * anchor.web3.LAMPORTS_PER_SOL), strikePrice: { value: new anchor.BN(25 * 10 ** 8), expo: -8 }, takerAmount: new anchor.BN(5 * anchor.web3.LAMPORTS_PER_SOL), participantPosition: { long: {} }, expiryTs: new anchor.BN(rejectedExpiryTs), acceptDeadline: new anchor.BN(rejectedExpiryTs - 59), atStrikeRule: { longWins: {} }, priceFeedId: new anchor.web3.PublicKey(solToUSD) });
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "InvalidAcceptDeadline");
+    }
+    assert(failed, "createBinaryOptions should fail when offers stay open until less than 60 seconds before expiry");
+  });
+
   it("Create Binary Options", async () => {
     // Add your test here.
     /*
@@ -143,25 +594,63 @@ describe("binary-options", () => {
     */
     let betDescription: string = 'A:SOL~P:LONG~S:$35~B:10SOL~T:5SOL';
     let betAmount = new anchor.BN(10 * anchor.web3.LAMPORTS_PER_SOL);
-    let strikePrice = new anchor.BN(25); // SOL price 25
+    let strikePrice = { value: new anchor.BN(25 * 10 ** 8), expo: -8 }; // SOL price 25.00000000, same exponent as the price feed
     let takerAmount = new anchor.BN(5 * anchor.web3.LAMPORTS_PER_SOL);
     let participantPosition = { long: {} };
+    // settlement is only possible at or after expiry
+    expiryTs = Math.floor(Date.now() / 1000) + 90;
+    let acceptDeadline = null; // defaults to 60 seconds before expiryTs
+    let atStrikeRule = { longWins: {} }; // Long wins if the settlement price is exactly at the strike
 
-    const tx = await program.methods.createBinaryOptions(betDescription, betAmount, strikePrice, takerAmount, participantPosition)
+    const tx = await program.methods.createBinaryOptions({ betDescription, betAmount, strikePrice, takerAmount, participantPosition, expiryTs: new anchor.BN(expiryTs), acceptDeadline, atStrikeRule, priceFeedId: new anchor.web3.PublicKey(solToUSD) })
       .accounts({
+        config: config,
         depositAccount: deposit_account.publicKey,
         pdaAuth: pda_auth,
-        solVault: sol_vault,
+        mint: mint,
+        tokenVault: token_vault,
+        depositTokenAccount: deposit_token_account,
         depositAuth: deposit_auth.publicKey,
-        adminDepositAccount: admin_deposit_account.publicKey,
+        adminDepositAccount: admin_deposit_account,
+        feedRegistry: feed_registry,
+        collateralRegistry: collateral_registry,
+        tokenProgram: TOKEN_PROGRAM_ID,
         systemProgram: anchor.web3.SystemProgram.programId,
       }).signers([deposit_account, deposit_auth]).rpc();
     console.log("Your transaction signature", tx);
 
     let result = await program.account.binaryOption.fetch(deposit_account.publicKey);
     console.log("createBinaryOptions: ", result);
+    console.log("betFees: ", result.betFees.toNumber() / anchor.web3.LAMPORTS_PER_SOL);
+    // the option is bound to the config and admin account it was created under
+    assert(result.config.equals(config));
+    assert(result.adminDepositAccount.equals(admin_deposit_account));
+    assert.equal(result.acceptDeadline.toNumber(), expiryTs - 60);
   });
   
+  it("Accept Binary Options without a position fails", async () => {
+    let failed = false;
+    try {
+      await program.methods.acceptBinaryOptions(new anchor.BN(5 * anchor.web3.LAMPORTS_PER_SOL), { unknown: {} })
+        .accounts({
+          config: config,
+          adminDepositAccount: admin_deposit_account,
+          adminPdaAuth: admin_pda_auth,
+          depositAccount: deposit_account.publicKey,
+          pdaAuth: pda_auth,
+          mint: mint,
+          tokenVault: token_vault,
+          depositTokenAccount: deposit_token_account_2,
+          depositAuth: deposit_auth_2.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        }).signers([deposit_auth_2]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "InvalidPrediction");
+    }
+    assert(failed, "acceptBinaryOptions should fail when the taker does not pick Long or Short");
+  });
+
   it("Accept Binary Options", async () => {
     // Add your test here.
     let amount = new anchor.BN(5 * anchor.web3.LAMPORTS_PER_SOL);
@@ -169,14 +658,16 @@ describe("binary-options", () => {
 
     const tx = await program.methods.acceptBinaryOptions(amount, participantPosition)
       .accounts({
-        adminDepositAccount: admin_deposit_account.publicKey,
+        config: config,
+        adminDepositAccount: admin_deposit_account,
         adminPdaAuth: admin_pda_auth,
-        adminSolVault: admin_sol_vault,
         depositAccount: deposit_account.publicKey,
         pdaAuth: pda_auth,
-        solVault: sol_vault,
+        mint: mint,
+        tokenVault: token_vault,
+        depositTokenAccount: deposit_token_account_2,
         depositAuth: deposit_auth_2.publicKey,
-        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
       }).signers([deposit_auth_2]).rpc();
     console.log("Your transaction signature", tx);
 
@@ -184,22 +675,152 @@ describe("binary-options", () => {
     console.log("acceptBinaryOptions: ", result);
   });
 
+  it("Close Binary Option before it is claimed fails", async () => {
+    let failed = false;
+    try {
+      await program.methods.closeBinaryOption()
+        .accounts({
+          config: config,
+          depositAccount: deposit_account.publicKey,
+          pdaAuth: pda_auth,
+          mint: mint,
+          tokenVault: token_vault,
+          depositTokenAccount: deposit_token_account,
+          depositAuth: deposit_auth.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        }).signers([deposit_auth]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "OptionNotClosable");
+    }
+    assert(failed, "closeBinaryOption should fail while the option is matched");
+
+    let result = await program.account.binaryOption.fetch(deposit_account.publicKey);
+    assert.deepEqual(result.bettingState, { matched: {} });
+  });
+
+  it("Void Binary Options before the grace period fails", async () => {
+    let failed = false;
+    try {
+      await program.methods.voidBinaryOptions()
+        .accounts({
+          config: config,
+          depositAccount: deposit_account.publicKey,
+          feedRegistry: feed_registry,
+          participant: deposit_auth_2.publicKey,
+        }).signers([deposit_auth_2]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "VoidGracePeriodNotElapsed");
+    }
+    assert(failed, "voidBinaryOptions should fail before the void grace period has elapsed");
+  });
+
+  it("Process Prediction with accounts not bound to the binary option fails", async () => {
+    const accounts = {
+      config: config,
+      pythPriceFeedAccount: new anchor.web3.PublicKey(solToUSD),
+      depositAccount: deposit_account.publicKey,
+      pdaAuth: pda_auth,
+      mint: mint,
+      tokenVault: token_vault,
+      adminDepositAccount: admin_deposit_account,
+      adminPdaAuth: admin_pda_auth,
+      adminTokenVault: admin_token_vault,
+      feedRegistry: feed_registry,
+      settler: settler.publicKey,
+      settlerTokenAccount: settler_token_account,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+
+    // another Pyth account than the price feed the option was created with, here the SOL/USD product
+    // account whose key is stored at offset 112 of the price account
+    let priceFeedData = (await provider.connection.getAccountInfo(new anchor.web3.PublicKey(solToUSD))).data;
+    let solToUSDProduct = new anchor.web3.PublicKey(priceFeedData.subarray(112, 144));
+    let failed = false;
+    try {
+      await program.methods.processPrediction()
+        .accounts({ ...accounts, pythPriceFeedAccount: solToUSDProduct })
+        .signers([settler]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "PriceFeedMismatch");
+    }
+    assert(failed, "processPrediction should fail with a foreign price feed");
+
+    // an account the oracle program does not own
+    failed = false;
+    try {
+      await program.methods.processPrediction()
+        .accounts({ ...accounts, pythPriceFeedAccount: feed_registry })
+        .signers([settler]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "InvalidOracleOwner");
+    }
+    assert(failed, "processPrediction should fail with a price feed not owned by the oracle program");
+
+    // the settler's own token account in place of the admin fee vault
+    failed = false;
+    try {
+      await program.methods.processPrediction()
+        .accounts({ ...accounts, adminTokenVault: deposit_token_account })
+        .signers([settler]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "ConstraintSeeds");
+    }
+    assert(failed, "processPrediction should fail when fees are routed to a foreign account");
+
+    // a keeper reward account the settler does not own
+    failed = false;
+    try {
+      await program.methods.processPrediction()
+        .accounts({ ...accounts, settlerTokenAccount: deposit_token_account })
+        .signers([settler]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "InvalidTokenAccountOwner");
+    }
+    assert(failed, "processPrediction should fail when the keeper reward is routed to a foreign account");
+
+    // settlement before expiry
+    if (Date.now() / 1000 < expiryTs) {
+      failed = false;
+      try {
+        await program.methods.processPrediction()
+          .accounts(accounts)
+          .signers([settler]).rpc();
+      } catch (error) {
+        failed = true;
+        assert.equal(error.error.errorCode.code, "OptionNotExpired");
+      }
+      assert(failed, "processPrediction should fail before expiry");
+    }
+  });
+
   it("Process Prediction", async () => {
     // Add your test here.
-    let betFees = new anchor.BN(3 * anchor.web3.LAMPORTS_PER_SOL);
+    let vaultBalanceBefore = (await getAccount(provider.connection, token_vault)).amount;
+    let adminVaultBalanceBefore = (await getAccount(provider.connection, admin_token_vault)).amount;
+    let settlerBalanceBefore = (await getAccount(provider.connection, settler_token_account)).amount;
 
-    const tx = await program.methods.processPrediction(betFees)
+    const tx = await settleAtExpiry(expiryTs, () => program.methods.processPrediction()
       .accounts({
-        config: config.publicKey,
+        config: config,
         pythPriceFeedAccount: new anchor.web3.PublicKey(solToUSD),
         depositAccount: deposit_account.publicKey,
         pdaAuth: pda_auth,
-        solVault: sol_vault,
-        adminDepositAccount: admin_deposit_account.publicKey,
+        mint: mint,
+        tokenVault: token_vault,
+        adminDepositAccount: admin_deposit_account,
         adminPdaAuth: admin_pda_auth,
-        adminSolVault: admin_sol_vault,
-        systemProgram: anchor.web3.SystemProgram.programId,
-      }).signers([]).rpc();
+        adminTokenVault: admin_token_vault,
+        feedRegistry: feed_registry,
+        settler: settler.publicKey,
+        settlerTokenAccount: settler_token_account,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([settler]).rpc());
     console.log("Your transaction signature", tx);
 
     let result = await program.account.binaryOption.fetch(deposit_account.publicKey);
@@ -208,44 +829,974 @@ describe("binary-options", () => {
     console.log("pythExpo: ", result.pythExpo);
     //console.log("price: ", result.actualPrice);
     console.log("pythPrice: ", result.pythPrice.toNumber());
-    console.log("actualPrice: ", result.actualPrice.toNumber());
+    console.log("actualPrice: ", result.actualPrice.value.toNumber(), "expo: ", result.actualPrice.expo);
+    console.log("outcome: ", result.outcome);
 	console.log("totalPayout: ", result.totalPayout.toNumber() / anchor.web3.LAMPORTS_PER_SOL);
+    assert(result.settledBy.equals(settler.publicKey));
+
+    // the maker holds Long and the taker Short. Long wins above the strike, Short below and, with
+    // atStrikeRule longWins, Long at the strike. Prices within pushToleranceBps of the strike are a push.
+    let strike = new anchor.BN(25 * 10 ** 8);
+    let distance = result.actualPrice.value.sub(strike).abs();
+    if (result.pushToleranceBps > 0 && distance.lte(strike.muln(result.pushToleranceBps).divn(10000))) {
+      assert.deepEqual(result.outcome, { push: {} });
+    } else if (result.actualPrice.value.gte(strike)) {
+      assert.deepEqual(result.outcome, { longWins: {} });
+      assert(result.winnerAuth.equals(deposit_auth.publicKey));
+    } else {
+      assert.deepEqual(result.outcome, { shortWins: {} });
+      assert(result.winnerAuth.equals(deposit_auth_2.publicKey));
+    }
+
+    // the settler receives 10% of the fee taken at settlement, the admin fee vault the rest
+    let fees = Number(vaultBalanceBefore) - Number((await getAccount(provider.connection, token_vault)).amount);
+    let adminFees = Number((await getAccount(provider.connection, admin_token_vault)).amount) - Number(adminVaultBalanceBefore);
+    let keeperReward = Number((await getAccount(provider.connection, settler_token_account)).amount) - Number(settlerBalanceBefore);
+    assert.equal(keeperReward, Math.floor(fees * 1000 / 10000));
+    assert.equal(adminFees + keeperReward, fees);
+  });
+
+  it("Process Prediction twice fails", async () => {
+    let vaultBalanceBefore = (await getAccount(provider.connection, token_vault)).amount;
+
+    let failed = false;
+    try {
+      await program.methods.processPrediction()
+        .accounts({
+          config: config,
+          pythPriceFeedAccount: new anchor.web3.PublicKey(solToUSD),
+          depositAccount: deposit_account.publicKey,
+          pdaAuth: pda_auth,
+          mint: mint,
+          tokenVault: token_vault,
+          adminDepositAccount: admin_deposit_account,
+          adminPdaAuth: admin_pda_auth,
+          adminTokenVault: admin_token_vault,
+          feedRegistry: feed_registry,
+          settler: settler.publicKey,
+          settlerTokenAccount: settler_token_account,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        }).signers([settler]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "AlreadySettled");
+    }
+    assert(failed, "processPrediction should fail on a settled binary option");
+
+    let vaultBalanceAfter = (await getAccount(provider.connection, token_vault)).amount;
+    assert.equal(vaultBalanceAfter, vaultBalanceBefore);
+  });
+
+  it("Accept Binary Options after settlement fails", async () => {
+    let failed = false;
+    try {
+      await program.methods.acceptBinaryOptions(new anchor.BN(5 * anchor.web3.LAMPORTS_PER_SOL), { short: {} })
+        .accounts({
+          config: config,
+          adminDepositAccount: admin_deposit_account,
+          adminPdaAuth: admin_pda_auth,
+          depositAccount: deposit_account.publicKey,
+          pdaAuth: pda_auth,
+          mint: mint,
+          tokenVault: token_vault,
+          depositTokenAccount: deposit_token_account_2,
+          depositAuth: deposit_auth_2.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        }).signers([deposit_auth_2]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "InvalidStateTransition");
+    }
+    assert(failed, "acceptBinaryOptions should fail once the option is settled");
   });
 
   it("Withdraw Participant Funds", async () => {
     // Add your test here.
-    let amount = new anchor.BN(12 * anchor.web3.LAMPORTS_PER_SOL);
+    // the winner withdraws the total payout (total stake less the fee fixed at creation)
+    let option = await program.account.binaryOption.fetch(deposit_account.publicKey);
+    let winner = option.winnerAuth.equals(deposit_auth.publicKey) ? deposit_auth : deposit_auth_2;
+    let winnerTokenAccount = option.winnerAuth.equals(deposit_auth.publicKey) ? deposit_token_account : deposit_token_account_2;
+    let amount = option.totalPayout;
 
     const tx = await program.methods.withdrawParticipantFunds(amount)
       .accounts({
+        config: config,
         depositAccount: deposit_account.publicKey,
         pdaAuth: pda_auth,
-        solVault: sol_vault,
+        mint: mint,
+        tokenVault: token_vault,
+        depositTokenAccount: winnerTokenAccount,
+        depositAuth: winner.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([winner]).rpc();
+    console.log("Your transaction signature", tx);
+
+    let result = await program.account.binaryOption.fetch(deposit_account.publicKey);
+    console.log("withdrawParticipantFunds: ", result);
+  });
+
+  it("Withdraw Participant Funds twice fails", async () => {
+    let option = await program.account.binaryOption.fetch(deposit_account.publicKey);
+    let winner = option.winnerAuth.equals(deposit_auth.publicKey) ? deposit_auth : deposit_auth_2;
+    let winnerTokenAccount = option.winnerAuth.equals(deposit_auth.publicKey) ? deposit_token_account : deposit_token_account_2;
+
+    let failed = false;
+    try {
+      await program.methods.withdrawParticipantFunds(option.totalPayout)
+        .accounts({
+          config: config,
+          depositAccount: deposit_account.publicKey,
+          pdaAuth: pda_auth,
+          mint: mint,
+          tokenVault: token_vault,
+          depositTokenAccount: winnerTokenAccount,
+          depositAuth: winner.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        }).signers([winner]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "AlreadyClaimed");
+    }
+    assert(failed, "withdrawParticipantFunds should fail once the payout is claimed");
+  });
+
+  it("Close Binary Option", async () => {
+    // the payout has been claimed, so the maker can reclaim the account rent
+    const tx = await program.methods.closeBinaryOption()
+      .accounts({
+        config: config,
+        depositAccount: deposit_account.publicKey,
+        pdaAuth: pda_auth,
+        mint: mint,
+        tokenVault: token_vault,
+        depositTokenAccount: deposit_token_account,
+        depositAuth: deposit_auth.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([deposit_auth]).rpc();
+    console.log("Your transaction signature", tx);
+
+    let result = await program.account.binaryOption.fetchNullable(deposit_account.publicKey);
+    assert.equal(result, null);
+    // the token vault is closed together with the binary option
+    assert.equal(await provider.connection.getAccountInfo(token_vault), null);
+  });
+
+  it("Cancel Binary Options", async () => {
+    let betAmount = new anchor.BN(2 * anchor.web3.LAMPORTS_PER_SOL);
+    let strikePrice = { value: new anchor.BN(25 * 10 ** 8), expo: -8 };
+    let takerAmount = new anchor.BN(1 * anchor.web3.LAMPORTS_PER_SOL);
+    let cancelExpiryTs = Math.floor(Date.now() / 1000) + 600;
+
+    await program.methods.createBinaryOptions({ betDescription: 'A:SOL~P:SHORT~S:$25~B:2SOL~T:1SOL', betAmount, strikePrice, takerAmount, participantPosition: { short: {} }, expiryTs: new anchor.BN(cancelExpiryTs), acceptDeadline: null, atStrikeRule: { longWins: {} }, priceFeedId: new anchor.web3.PublicKey(solToUSD) })
+      .accounts({
+        config: config,
+        depositAccount: cancel_deposit_account.publicKey,
+        pdaAuth: cancel_pda_auth,
+        mint: mint,
+        tokenVault: cancel_token_vault,
+        depositTokenAccount: deposit_token_account,
         depositAuth: deposit_auth.publicKey,
+        adminDepositAccount: admin_deposit_account,
+        feedRegistry: feed_registry,
+        collateralRegistry: collateral_registry,
+        tokenProgram: TOKEN_PROGRAM_ID,
         systemProgram: anchor.web3.SystemProgram.programId,
+      }).signers([cancel_deposit_account, deposit_auth]).rpc();
+
+    // nobody accepted the offer, so the maker takes the stake back
+    const tx = await program.methods.cancelBinaryOptions()
+      .accounts({
+        depositAccount: cancel_deposit_account.publicKey,
+        pdaAuth: cancel_pda_auth,
+        mint: mint,
+        tokenVault: cancel_token_vault,
+        depositTokenAccount: deposit_token_account,
+        depositAuth: deposit_auth.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
       }).signers([deposit_auth]).rpc();
     console.log("Your transaction signature", tx);
 
-    let result = await program.account.binaryOption.fetch(deposit_account.publicKey);
-    console.log("withdrawParticipantFunds: ", result);
+    let result = await program.account.binaryOption.fetchNullable(cancel_deposit_account.publicKey);
+    assert.equal(result, null);
+    // the token vault is closed together with the binary option
+    assert.equal(await provider.connection.getAccountInfo(cancel_token_vault), null);
+  });
+
+  it("Expire Binary Options", async () => {
+    let betAmount = new anchor.BN(2 * anchor.web3.LAMPORTS_PER_SOL);
+    let strikePrice = { value: new anchor.BN(25 * 10 ** 8), expo: -8 };
+    let takerAmount = new anchor.BN(1 * anchor.web3.LAMPORTS_PER_SOL);
+    let now = Math.floor(Date.now() / 1000);
+    let acceptDeadline = now + 5;
+
+    await program.methods.createBinaryOptions({ betDescription: 'A:SOL~P:LONG~S:$25~B:2SOL~T:1SOL', betAmount, strikePrice, takerAmount, participantPosition: { long: {} }, expiryTs: new anchor.BN(now + 600), acceptDeadline: new anchor.BN(acceptDeadline), atStrikeRule: { longWins: {} }, priceFeedId: new anchor.web3.PublicKey(solToUSD) })
+      .accounts({
+        config: config,
+        depositAccount: expire_deposit_account.publicKey,
+        pdaAuth: expire_pda_auth,
+        mint: mint,
+        tokenVault: expire_token_vault,
+        depositTokenAccount: deposit_token_account,
+        depositAuth: deposit_auth.publicKey,
+        adminDepositAccount: admin_deposit_account,
+        feedRegistry: feed_registry,
+        collateralRegistry: collateral_registry,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      }).signers([expire_deposit_account, deposit_auth]).rpc();
+
+    // wait until the accept deadline has passed
+    await new Promise((resolve) => setTimeout(resolve, (acceptDeadline + 2) * 1000 - Date.now()));
+
+    let failed = false;
+    try {
+      await program.methods.acceptBinaryOptions(takerAmount, { short: {} })
+        .accounts({
+          config: config,
+          adminDepositAccount: admin_deposit_account,
+          adminPdaAuth: admin_pda_auth,
+          depositAccount: expire_deposit_account.publicKey,
+          pdaAuth: expire_pda_auth,
+          mint: mint,
+          tokenVault: expire_token_vault,
+          depositTokenAccount: deposit_token_account_2,
+          depositAuth: deposit_auth_2.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        }).signers([deposit_auth_2]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "AcceptDeadlinePassed");
+    }
+    assert(failed, "acceptBinaryOptions should fail after the accept deadline");
+
+    // anyone can expire the offer, the stake goes back to the maker
+    const tx = await program.methods.expireBinaryOptions()
+      .accounts({
+        depositAccount: expire_deposit_account.publicKey,
+        pdaAuth: expire_pda_auth,
+        mint: mint,
+        tokenVault: expire_token_vault,
+        depositTokenAccount: deposit_token_account,
+        depositAuth: deposit_auth.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([]).rpc();
+    console.log("Your transaction signature", tx);
+
+    let result = await program.account.binaryOption.fetch(expire_deposit_account.publicKey);
+    assert.deepEqual(result.bettingState, { expired: {} });
+    assert.equal((await getAccount(provider.connection, expire_token_vault)).amount, BigInt(0));
+  });
+
+  it("Process Prediction with a later price in the settlement window fails", async () => {
+    const later_deposit_account = anchor.web3.Keypair.generate();
+    let [later_pda_auth] = anchor.web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("auth"),
+      later_deposit_account.publicKey.toBuffer()
+      ],
+      program.programId);
+    let [later_token_vault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("token_vault"),
+      later_pda_auth.toBuffer()
+      ],
+      program.programId);
+
+    let betAmount = new anchor.BN(2 * anchor.web3.LAMPORTS_PER_SOL);
+    let strikePrice = { value: new anchor.BN(25 * 10 ** 8), expo: -8 };
+    let takerAmount = new anchor.BN(1 * anchor.web3.LAMPORTS_PER_SOL);
+    let laterExpiryTs = Math.floor(Date.now() / 1000) + 80;
+
+    await program.methods.createBinaryOptions({ betDescription: 'A:SOL~P:LONG~S:$25~B:2SOL~T:1SOL', betAmount, strikePrice, takerAmount, participantPosition: { long: {} }, expiryTs: new anchor.BN(laterExpiryTs), acceptDeadline: null, atStrikeRule: { longWins: {} }, priceFeedId: new anchor.web3.PublicKey(solToUSD) })
+      .accounts({
+        config: config,
+        depositAccount: later_deposit_account.publicKey,
+        pdaAuth: later_pda_auth,
+        mint: mint,
+        tokenVault: later_token_vault,
+        depositTokenAccount: deposit_token_account,
+        depositAuth: deposit_auth.publicKey,
+        adminDepositAccount: admin_deposit_account,
+        feedRegistry: feed_registry,
+        collateralRegistry: collateral_registry,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      }).signers([later_deposit_account, deposit_auth]).rpc();
+
+    await program.methods.acceptBinaryOptions(takerAmount, { short: {} })
+      .accounts({
+        config: config,
+        adminDepositAccount: admin_deposit_account,
+        adminPdaAuth: admin_pda_auth,
+        depositAccount: later_deposit_account.publicKey,
+        pdaAuth: later_pda_auth,
+        mint: mint,
+        tokenVault: later_token_vault,
+        depositTokenAccount: deposit_token_account_2,
+        depositAuth: deposit_auth_2.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([deposit_auth_2]).rpc();
+
+    // Pyth has published several prices since expiry, still well inside the 30 seconds settlement window
+    await new Promise((resolve) => setTimeout(resolve, (laterExpiryTs + 10) * 1000 - Date.now()));
+
+    let failed = false;
+    try {
+      await program.methods.processPrediction()
+        .accounts({
+          config: config,
+          pythPriceFeedAccount: new anchor.web3.PublicKey(solToUSD),
+          depositAccount: later_deposit_account.publicKey,
+          pdaAuth: later_pda_auth,
+          mint: mint,
+          tokenVault: later_token_vault,
+          adminDepositAccount: admin_deposit_account,
+          adminPdaAuth: admin_pda_auth,
+          adminTokenVault: admin_token_vault,
+          feedRegistry: feed_registry,
+          settler: settler.publicKey,
+          settlerTokenAccount: settler_token_account,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        }).signers([settler]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "SettlementPriceOutsideWindow");
+    }
+    assert(failed, "processPrediction should only accept the first price published at or after expiry");
+
+    let result = await program.account.binaryOption.fetch(later_deposit_account.publicKey);
+    assert.deepEqual(result.bettingState, { matched: {} });
+  });
+
+  it("Process Prediction after the settlement window fails", async () => {
+    // the shortest void grace period the admin can set, the option keeps it when the config changes later
+    const lateConfig = {
+      oracleProgramId: new anchor.web3.PublicKey(pythOracle),
+      confMultiplier: 2,
+      tooClosePolicy: { refund: {} },
+      pushToleranceBps: 10,
+      pushFeeBps: 100,
+      voidGracePeriod: new anchor.BN(30),
+      keeperRewardBps: 1000,
+      guardian: guardian.publicKey,
+    };
+    await program.methods.updateConfig(lateConfig)
+      .accounts({
+        config: config,
+        adminDepositAccount: admin_deposit_account,
+        adminAuth: admin_auth.publicKey,
+      }).signers([admin_auth]).rpc();
+
+    let betAmount = new anchor.BN(2 * anchor.web3.LAMPORTS_PER_SOL);
+    let strikePrice = { value: new anchor.BN(25 * 10 ** 8), expo: -8 };
+    let takerAmount = new anchor.BN(1 * anchor.web3.LAMPORTS_PER_SOL);
+    let lateExpiryTs = Math.floor(Date.now() / 1000) + 80;
+
+    await program.methods.createBinaryOptions({ betDescription: 'A:SOL~P:LONG~S:$25~B:2SOL~T:1SOL', betAmount, strikePrice, takerAmount, participantPosition: { long: {} }, expiryTs: new anchor.BN(lateExpiryTs), acceptDeadline: null, atStrikeRule: { longWins: {} }, priceFeedId: new anchor.web3.PublicKey(solToUSD) })
+      .accounts({
+        config: config,
+        depositAccount: late_deposit_account.publicKey,
+        pdaAuth: late_pda_auth,
+        mint: mint,
+        tokenVault: late_token_vault,
+        depositTokenAccount: deposit_token_account,
+        depositAuth: deposit_auth.publicKey,
+        adminDepositAccount: admin_deposit_account,
+        feedRegistry: feed_registry,
+        collateralRegistry: collateral_registry,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      }).signers([late_deposit_account, deposit_auth]).rpc();
+
+    // restore the void grace period
+    await program.methods.updateConfig({ ...lateConfig, voidGracePeriod: new anchor.BN(7200) })
+      .accounts({
+        config: config,
+        adminDepositAccount: admin_deposit_account,
+        adminAuth: admin_auth.publicKey,
+      }).signers([admin_auth]).rpc();
+
+    await program.methods.acceptBinaryOptions(takerAmount, { short: {} })
+      .accounts({
+        config: config,
+        adminDepositAccount: admin_deposit_account,
+        adminPdaAuth: admin_pda_auth,
+        depositAccount: late_deposit_account.publicKey,
+        pdaAuth: late_pda_auth,
+        mint: mint,
+        tokenVault: late_token_vault,
+        depositTokenAccount: deposit_token_account_2,
+        depositAuth: deposit_auth_2.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([deposit_auth_2]).rpc();
+
+    // Pyth keeps publishing, so by now the latest price was published after the 30 seconds settlement window
+    await new Promise((resolve) => setTimeout(resolve, (lateExpiryTs + 40) * 1000 - Date.now()));
+
+    let failed = false;
+    try {
+      await program.methods.processPrediction()
+        .accounts({
+          config: config,
+          pythPriceFeedAccount: new anchor.web3.PublicKey(solToUSD),
+          depositAccount: late_deposit_account.publicKey,
+          pdaAuth: late_pda_auth,
+          mint: mint,
+          tokenVault: late_token_vault,
+          adminDepositAccount: admin_deposit_account,
+          adminPdaAuth: admin_pda_auth,
+          adminTokenVault: admin_token_vault,
+          feedRegistry: feed_registry,
+          settler: settler.publicKey,
+          settlerTokenAccount: settler_token_account,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        }).signers([settler]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "SettlementPriceOutsideWindow");
+    }
+    assert(failed, "processPrediction should fail with a price published after the settlement window");
+
+    let result = await program.account.binaryOption.fetch(late_deposit_account.publicKey);
+    assert.deepEqual(result.bettingState, { matched: {} });
+    assert.equal(result.voidGracePeriod.toNumber(), 30);
+  });
+
+  it("Void Binary Options refunds both participants in full", async () => {
+    let option = await program.account.binaryOption.fetch(late_deposit_account.publicKey);
+
+    // the option cannot be voided before the settlement window and the feed's 60 seconds staleness threshold have passed
+    await new Promise((resolve) => setTimeout(resolve, (option.expiryTs.toNumber() + 30 + 60 + 2) * 1000 - Date.now()));
+
+    const tx = await program.methods.voidBinaryOptions()
+      .accounts({
+        config: config,
+        depositAccount: late_deposit_account.publicKey,
+        feedRegistry: feed_registry,
+        participant: deposit_auth_2.publicKey,
+      }).signers([deposit_auth_2]).rpc();
+    console.log("Your transaction signature", tx);
+
+    option = await program.account.binaryOption.fetch(late_deposit_account.publicKey);
+    assert.deepEqual(option.bettingState, { voided: {} });
+    assert.deepEqual(option.outcome, { refund: {} });
+
+    // no fee is taken, each participant withdraws their whole stake
+    let makerBalanceBefore = (await getAccount(provider.connection, deposit_token_account)).amount;
+    await program.methods.withdrawParticipantFunds(option.betAmount)
+      .accounts({
+        config: config,
+        depositAccount: late_deposit_account.publicKey,
+        pdaAuth: late_pda_auth,
+        mint: mint,
+        tokenVault: late_token_vault,
+        depositTokenAccount: deposit_token_account,
+        depositAuth: deposit_auth.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([deposit_auth]).rpc();
+    let makerBalanceAfter = (await getAccount(provider.connection, deposit_token_account)).amount;
+    assert.equal(makerBalanceAfter - makerBalanceBefore, BigInt(option.betAmount.toString()));
+
+    let takerBalanceBefore = (await getAccount(provider.connection, deposit_token_account_2)).amount;
+    await program.methods.withdrawParticipantFunds(option.takerAmount)
+      .accounts({
+        config: config,
+        depositAccount: late_deposit_account.publicKey,
+        pdaAuth: late_pda_auth,
+        mint: mint,
+        tokenVault: late_token_vault,
+        depositTokenAccount: deposit_token_account_2,
+        depositAuth: deposit_auth_2.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([deposit_auth_2]).rpc();
+    let takerBalanceAfter = (await getAccount(provider.connection, deposit_token_account_2)).amount;
+    assert.equal(takerBalanceAfter - takerBalanceBefore, BigInt(option.takerAmount.toString()));
+
+    option = await program.account.binaryOption.fetch(late_deposit_account.publicKey);
+    assert.deepEqual(option.bettingState, { claimed: {} });
+    assert.equal((await getAccount(provider.connection, late_token_vault)).amount, BigInt(0));
+  });
+
+  it("Push refunds each participant less the capped push fee", async () => {
+    const push_deposit_account = anchor.web3.Keypair.generate();
+    let [push_pda_auth] = anchor.web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("auth"),
+      push_deposit_account.publicKey.toBuffer()
+      ],
+      program.programId);
+    let [push_token_vault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("token_vault"),
+      push_pda_auth.toBuffer()
+      ],
+      program.programId);
+
+    // a 100% push band around the strike, any settlement price up to twice the strike is a push.
+    // Without a keeper reward the settler needs no token account of the mint.
+    const pushConfig = {
+      oracleProgramId: new anchor.web3.PublicKey(pythOracle),
+      confMultiplier: 2,
+      tooClosePolicy: { refund: {} },
+      pushToleranceBps: 10000,
+      pushFeeBps: 100,
+      voidGracePeriod: new anchor.BN(7200),
+      keeperRewardBps: 0,
+      guardian: guardian.publicKey,
+    };
+    await program.methods.updateConfig(pushConfig)
+      .accounts({
+        config: config,
+        adminDepositAccount: admin_deposit_account,
+        adminAuth: admin_auth.publicKey,
+      }).signers([admin_auth]).rpc();
+
+    let betAmount = new anchor.BN(2 * anchor.web3.LAMPORTS_PER_SOL);
+    let strikePrice = { value: new anchor.BN("1000000000000000"), expo: -8 }; // SOL price 10,000,000.00000000
+    let takerAmount = new anchor.BN(1 * anchor.web3.LAMPORTS_PER_SOL);
+    let pushExpiryTs = Math.floor(Date.now() / 1000) + 80;
+
+    await program.methods.createBinaryOptions({ betDescription: 'A:SOL~P:LONG~S:$10M~B:2SOL~T:1SOL', betAmount, strikePrice, takerAmount, participantPosition: { long: {} }, expiryTs: new anchor.BN(pushExpiryTs), acceptDeadline: null, atStrikeRule: { longWins: {} }, priceFeedId: new anchor.web3.PublicKey(solToUSD) })
+      .accounts({
+        config: config,
+        depositAccount: push_deposit_account.publicKey,
+        pdaAuth: push_pda_auth,
+        mint: mint,
+        tokenVault: push_token_vault,
+        depositTokenAccount: deposit_token_account,
+        depositAuth: deposit_auth.publicKey,
+        adminDepositAccount: admin_deposit_account,
+        feedRegistry: feed_registry,
+        collateralRegistry: collateral_registry,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      }).signers([push_deposit_account, deposit_auth]).rpc();
+
+    await program.methods.acceptBinaryOptions(takerAmount, { short: {} })
+      .accounts({
+        config: config,
+        adminDepositAccount: admin_deposit_account,
+        adminPdaAuth: admin_pda_auth,
+        depositAccount: push_deposit_account.publicKey,
+        pdaAuth: push_pda_auth,
+        mint: mint,
+        tokenVault: push_token_vault,
+        depositTokenAccount: deposit_token_account_2,
+        depositAuth: deposit_auth_2.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([deposit_auth_2]).rpc();
+
+    await settleAtExpiry(pushExpiryTs, () => program.methods.processPrediction()
+      .accounts({
+        config: config,
+        pythPriceFeedAccount: new anchor.web3.PublicKey(solToUSD),
+        depositAccount: push_deposit_account.publicKey,
+        pdaAuth: push_pda_auth,
+        mint: mint,
+        tokenVault: push_token_vault,
+        adminDepositAccount: admin_deposit_account,
+        adminPdaAuth: admin_pda_auth,
+        adminTokenVault: admin_token_vault,
+        feedRegistry: feed_registry,
+        settler: settler.publicKey,
+        settlerTokenAccount: settler.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([settler]).rpc());
+
+    // restore the push band and the keeper reward
+    await program.methods.updateConfig({ ...pushConfig, pushToleranceBps: 10, keeperRewardBps: 1000 })
+      .accounts({
+        config: config,
+        adminDepositAccount: admin_deposit_account,
+        adminAuth: admin_auth.publicKey,
+      }).signers([admin_auth]).rpc();
+
+    let option = await program.account.binaryOption.fetch(push_deposit_account.publicKey);
+    assert.deepEqual(option.outcome, { push: {} });
+    assert.equal(option.totalPayout.toNumber(), 0);
+
+    // each participant pays push_fee_bps of their stake, never more than their share of the option's fee
+    let totalStake = betAmount.add(takerAmount);
+    let pushFee = (stake: anchor.BN) => anchor.BN.min(
+      stake.muln(option.pushFeeBps).divn(10000),
+      option.betFees.mul(stake).div(totalStake));
+    let makerRefund = betAmount.sub(pushFee(betAmount));
+    let takerRefund = takerAmount.sub(pushFee(takerAmount));
+    assert(pushFee(betAmount).add(pushFee(takerAmount)).lte(option.betFees));
+    assert.equal((await getAccount(provider.connection, push_token_vault)).amount, BigInt(makerRefund.add(takerRefund).toString()));
+
+    // the participants claim separately, the option is claimed once both have withdrawn
+    await program.methods.withdrawParticipantFunds(makerRefund)
+      .accounts({
+        config: config,
+        depositAccount: push_deposit_account.publicKey,
+        pdaAuth: push_pda_auth,
+        mint: mint,
+        tokenVault: push_token_vault,
+        depositTokenAccount: deposit_token_account,
+        depositAuth: deposit_auth.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([deposit_auth]).rpc();
+
+    option = await program.account.binaryOption.fetch(push_deposit_account.publicKey);
+    assert.deepEqual(option.bettingState, { settled: {} });
+    assert(option.firstParticipantClaimed);
+
+    await program.methods.withdrawParticipantFunds(takerRefund)
+      .accounts({
+        config: config,
+        depositAccount: push_deposit_account.publicKey,
+        pdaAuth: push_pda_auth,
+        mint: mint,
+        tokenVault: push_token_vault,
+        depositTokenAccount: deposit_token_account_2,
+        depositAuth: deposit_auth_2.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([deposit_auth_2]).rpc();
+
+    option = await program.account.binaryOption.fetch(push_deposit_account.publicKey);
+    assert.deepEqual(option.bettingState, { claimed: {} });
+    assert.equal((await getAccount(provider.connection, push_token_vault)).amount, BigInt(0));
+  });
+
+  it("Create Binary Options with a Token-2022 transfer fee mint", async () => {
+    // Token-2022 mint charging 1% on every transfer, reused when an earlier run registered it
+    let fee_mint = await registeredMint(TOKEN_2022_PROGRAM_ID);
+    let registered = fee_mint !== null;
+    if (!registered) {
+      const fee_mint_keypair = anchor.web3.Keypair.generate();
+      fee_mint = fee_mint_keypair.publicKey;
+      const mintLen = getMintLen([ExtensionType.TransferFeeConfig]);
+      const lamports = await provider.connection.getMinimumBalanceForRentExemption(mintLen);
+      const createMintTx = new anchor.web3.Transaction().add(
+        anchor.web3.SystemProgram.createAccount({
+          fromPubkey: admin_auth.publicKey,
+          newAccountPubkey: fee_mint,
+          space: mintLen,
+          lamports,
+          programId: TOKEN_2022_PROGRAM_ID,
+        }),
+        createInitializeTransferFeeConfigInstruction(fee_mint, admin_auth.publicKey, admin_auth.publicKey, 100, BigInt(anchor.web3.LAMPORTS_PER_SOL), TOKEN_2022_PROGRAM_ID),
+        createInitializeMintInstruction(fee_mint, 9, admin_auth.publicKey, null, TOKEN_2022_PROGRAM_ID)
+      );
+      await anchor.web3.sendAndConfirmTransaction(provider.connection, createMintTx, [admin_auth, fee_mint_keypair]);
+    }
+
+    let fee_token_account = await createAccount(provider.connection, deposit_auth, fee_mint, deposit_auth.publicKey, undefined, undefined, TOKEN_2022_PROGRAM_ID);
+    await mintTo(provider.connection, admin_auth, fee_mint, fee_token_account, admin_auth, 10 * anchor.web3.LAMPORTS_PER_SOL, [], undefined, TOKEN_2022_PROGRAM_ID);
+
+    let [fee_pda_auth] = anchor.web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("auth"),
+      fee_deposit_account.publicKey.toBuffer()
+      ],
+      program.programId);
+    let [fee_token_vault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("token_vault"),
+      fee_pda_auth.toBuffer()
+      ],
+      program.programId);
+    let [fee_admin_token_vault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("admin_token_vault"),
+      admin_pda_auth.toBuffer(),
+      fee_mint.toBuffer()
+      ],
+      program.programId);
+
+    if (!registered) {
+      await program.methods.addCollateralMint(
+        {
+          mint: fee_mint,
+          enabled: true,
+          minStake: new anchor.BN(0.1 * anchor.web3.LAMPORTS_PER_SOL),
+          maxStake: new anchor.BN(50 * anchor.web3.LAMPORTS_PER_SOL),
+          feeBps: 500,
+          minFee: new anchor.BN(0.01 * anchor.web3.LAMPORTS_PER_SOL),
+          maxFee: new anchor.BN(3 * anchor.web3.LAMPORTS_PER_SOL),
+          decimals: 9,
+        }
+      )
+        .accounts({
+          adminDepositAccount: admin_deposit_account,
+          collateralRegistry: collateral_registry,
+          adminPdaAuth: admin_pda_auth,
+          mint: fee_mint,
+          adminTokenVault: fee_admin_token_vault,
+          adminAuth: admin_auth.publicKey,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        }).signers([admin_auth]).rpc();
+    }
+
+    let betAmount = new anchor.BN(2 * anchor.web3.LAMPORTS_PER_SOL);
+    let strikePrice = { value: new anchor.BN(25 * 10 ** 8), expo: -8 };
+    let takerAmount = new anchor.BN(1 * anchor.web3.LAMPORTS_PER_SOL);
+    let balanceBefore = (await getAccount(provider.connection, fee_token_account, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+
+    await program.methods.createBinaryOptions({ betDescription: 'A:SOL~P:LONG~S:$25~B:2USD~T:1USD', betAmount, strikePrice, takerAmount, participantPosition: { long: {} }, expiryTs: new anchor.BN(Math.floor(Date.now() / 1000) + 600), acceptDeadline: null, atStrikeRule: { longWins: {} }, priceFeedId: new anchor.web3.PublicKey(solToUSD) })
+      .accounts({
+        config: config,
+        depositAccount: fee_deposit_account.publicKey,
+        pdaAuth: fee_pda_auth,
+        mint: fee_mint,
+        tokenVault: fee_token_vault,
+        depositTokenAccount: fee_token_account,
+        depositAuth: deposit_auth.publicKey,
+        adminDepositAccount: admin_deposit_account,
+        feedRegistry: feed_registry,
+        collateralRegistry: collateral_registry,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      }).signers([fee_deposit_account, deposit_auth]).rpc();
+
+    // the vault holds exactly the recorded stake, the maker paid the transfer fee on top
+    let result = await program.account.binaryOption.fetch(fee_deposit_account.publicKey);
+    let vault = await getAccount(provider.connection, fee_token_vault, undefined, TOKEN_2022_PROGRAM_ID);
+    assert.equal(vault.amount.toString(), result.betAmount.toString());
+    let balanceAfter = (await getAccount(provider.connection, fee_token_account, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+    assert(balanceBefore - balanceAfter > BigInt(betAmount.toString()), "the maker pays the transfer fee on top of the stake");
+
+    // withheld transfer fees are harvested so the vault can be closed on cancel
+    await program.methods.cancelBinaryOptions()
+      .accounts({
+        depositAccount: fee_deposit_account.publicKey,
+        pdaAuth: fee_pda_auth,
+        mint: fee_mint,
+        tokenVault: fee_token_vault,
+        depositTokenAccount: fee_token_account,
+        depositAuth: deposit_auth.publicKey,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      }).signers([deposit_auth]).rpc();
+
+    assert.equal(await provider.connection.getAccountInfo(fee_token_vault), null);
+  });
+
+  it("Pause and unpause the program", async () => {
+    // an unmatched offer created before the incident
+    const paused_deposit_account = anchor.web3.Keypair.generate();
+    let [paused_pda_auth] = anchor.web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("auth"),
+      paused_deposit_account.publicKey.toBuffer()
+      ],
+      program.programId);
+    let [paused_token_vault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("token_vault"),
+      paused_pda_auth.toBuffer()
+      ],
+      program.programId);
+
+    await program.methods.createBinaryOptions({ betDescription: 'A:SOL~P:LONG~S:$25~B:1SOL~T:1SOL', betAmount: new anchor.BN(1 * anchor.web3.LAMPORTS_PER_SOL), strikePrice: { value: new anchor.BN(25 * 10 ** 8), expo: -8 }, takerAmount: new anchor.BN(1 * anchor.web3.LAMPORTS_PER_SOL), participantPosition: { long: {} }, expiryTs: new anchor.BN(Math.floor(Date.now() / 1000) + 600), acceptDeadline: null, atStrikeRule: { longWins: {} }, priceFeedId: new anchor.web3.PublicKey(solToUSD) })
+      .accounts({
+        config: config,
+        depositAccount: paused_deposit_account.publicKey,
+        pdaAuth: paused_pda_auth,
+        mint: mint,
+        tokenVault: paused_token_vault,
+        depositTokenAccount: deposit_token_account,
+        depositAuth: deposit_auth.publicKey,
+        adminDepositAccount: admin_deposit_account,
+        feedRegistry: feed_registry,
+        collateralRegistry: collateral_registry,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      }).signers([paused_deposit_account, deposit_auth]).rpc();
+
+    try {
+      // the guardian halts everything during an incident
+      await program.methods.setPauseFlags({ all: true, create: false, accept: false, settle: false, withdraw: false })
+        .accounts({
+          config: config,
+          adminDepositAccount: admin_deposit_account,
+          authority: guardian.publicKey,
+        }).signers([guardian]).rpc();
+
+      let failed = false;
+      try {
+        await program.methods.withdraw(new anchor.BN(1))
+          .accounts({
+            config: config,
+            adminDepositAccount: admin_deposit_account,
+            adminPdaAuth: admin_pda_auth,
+            mint: mint,
+            adminTokenVault: admin_token_vault,
+            adminTokenAccount: admin_token_account,
+            adminAuth: admin_auth.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          }).signers([admin_auth]).rpc();
+      } catch (error) {
+        failed = true;
+        assert.equal(error.error.errorCode.code, "ProtocolPaused");
+      }
+      assert(failed, "withdraw should fail while the program is paused");
+
+      // the maker of an unmatched offer can always take the stake back
+      await program.methods.cancelBinaryOptions()
+        .accounts({
+          depositAccount: paused_deposit_account.publicKey,
+          pdaAuth: paused_pda_auth,
+          mint: mint,
+          tokenVault: paused_token_vault,
+          depositTokenAccount: deposit_token_account,
+          depositAuth: deposit_auth.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        }).signers([deposit_auth]).rpc();
+      assert.equal(await provider.connection.getAccountInfo(paused_token_vault), null);
+
+      // only the admin or the guardian can change the pause flags
+      failed = false;
+      try {
+        await program.methods.setPauseFlags({ all: false, create: false, accept: false, settle: false, withdraw: false })
+          .accounts({
+            config: config,
+            adminDepositAccount: admin_deposit_account,
+            authority: deposit_auth.publicKey,
+          }).signers([deposit_auth]).rpc();
+      } catch (error) {
+        failed = true;
+        assert.equal(error.error.errorCode.code, "Unauthorized");
+      }
+      assert(failed, "setPauseFlags should fail when not signed by the admin or the guardian");
+
+      const tx = await program.methods.setPauseFlags({ all: false, create: false, accept: false, settle: false, withdraw: false })
+        .accounts({
+          config: config,
+          adminDepositAccount: admin_deposit_account,
+          authority: admin_auth.publicKey,
+        }).signers([admin_auth]).rpc();
+      console.log("Your transaction signature", tx);
+
+    } finally {
+      // later tests and runs need an unpaused program, even when the test failed while paused
+      let current = await program.account.adminConfig.fetch(config);
+      if (Object.values(current.pauseFlags).some((paused) => paused)) {
+        await program.methods.setPauseFlags({ all: false, create: false, accept: false, settle: false, withdraw: false })
+          .accounts({
+            config: config,
+            adminDepositAccount: admin_deposit_account,
+            authority: admin_auth.publicKey,
+          }).signers([admin_auth]).rpc();
+      }
+    }
+
+    let result = await program.account.adminConfig.fetch(config);
+    assert.deepEqual(result.pauseFlags, { all: false, create: false, accept: false, settle: false, withdraw: false });
   });
 
   it("Withdraw", async () => {
     // Add your test here.
-    let amount = new anchor.BN(1 * anchor.web3.LAMPORTS_PER_SOL);
+    // the house withdraws the fees collected in the collateral token
+    let fees = (await getAccount(provider.connection, admin_token_vault)).amount;
+    let amount = new anchor.BN(fees.toString());
 
     const tx = await program.methods.withdraw(amount)
       .accounts({
-        adminDepositAccount: admin_deposit_account.publicKey,
+        config: config,
+        adminDepositAccount: admin_deposit_account,
         adminPdaAuth: admin_pda_auth,
-        adminSolVault: admin_sol_vault,
+        mint: mint,
+        adminTokenVault: admin_token_vault,
+        adminTokenAccount: admin_token_account,
         adminAuth: admin_auth.publicKey,
-        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
       }).signers([admin_auth]).rpc();
     console.log("Your transaction signature", tx);
 
-    let result = await program.account.depositBaseAdmin.fetch(admin_deposit_account.publicKey);
+    let result = await program.account.depositBaseAdmin.fetch(admin_deposit_account);
     console.log("withdraw: ", result);
   });
 
+  it("Cancel Admin Proposal", async () => {
+    const new_admin_auth = anchor.web3.Keypair.generate();
+
+    await program.methods.proposeAdmin(new_admin_auth.publicKey)
+      .accounts({
+        adminDepositAccount: admin_deposit_account,
+        adminAuth: admin_auth.publicKey,
+      }).signers([admin_auth]).rpc();
+
+    const tx = await program.methods.cancelAdminProposal()
+      .accounts({
+        adminDepositAccount: admin_deposit_account,
+        adminAuth: admin_auth.publicKey,
+      }).signers([admin_auth]).rpc();
+    console.log("Your transaction signature", tx);
+
+    // the cancelled proposal can no longer be accepted
+    let failed = false;
+    try {
+      await program.methods.acceptAdmin()
+        .accounts({
+          adminDepositAccount: admin_deposit_account,
+          pendingAdminAuth: new_admin_auth.publicKey,
+        }).signers([new_admin_auth]).rpc();
+    } catch (error) {
+      failed = true;
+      assert.equal(error.error.errorCode.code, "NotPendingAdmin");
+    }
+    assert(failed, "acceptAdmin should fail once the proposal is cancelled");
+
+    let result = await program.account.depositBaseAdmin.fetch(admin_deposit_account);
+    assert(result.adminAuth.equals(admin_auth.publicKey));
+    assert.equal(result.pendingAdminAuth, null);
+  });
+
+  it("Transfer Admin Authority", async () => {
+    const new_admin_auth = anchor.web3.Keypair.generate();
+
+    try {
+      await program.methods.proposeAdmin(new_admin_auth.publicKey)
+        .accounts({
+          adminDepositAccount: admin_deposit_account,
+          adminAuth: admin_auth.publicKey,
+        }).signers([admin_auth]).rpc();
+
+      // only the proposed authority can accept
+      let failed = false;
+      try {
+        await program.methods.acceptAdmin()
+          .accounts({
+            adminDepositAccount: admin_deposit_account,
+            pendingAdminAuth: deposit_auth.publicKey,
+          }).signers([deposit_auth]).rpc();
+      } catch (error) {
+        failed = true;
+        assert.equal(error.error.errorCode.code, "NotPendingAdmin");
+      }
+      assert(failed, "acceptAdmin should fail when not signed by the proposed authority");
+
+      const tx = await program.methods.acceptAdmin()
+        .accounts({
+          adminDepositAccount: admin_deposit_account,
+          pendingAdminAuth: new_admin_auth.publicKey,
+        }).signers([new_admin_auth]).rpc();
+      console.log("Your transaction signature", tx);
+
+      let result = await program.account.depositBaseAdmin.fetch(admin_deposit_account);
+      assert(result.adminAuth.equals(new_admin_auth.publicKey));
+      assert.equal(result.pendingAdminAuth, null);
+    } finally {
+      // hand the authority back so the deployment keeps its admin for the next run, even when the test failed.
+      // new_admin_auth only lives in this test, the admin would be lost with it.
+      let admin = await program.account.depositBaseAdmin.fetch(admin_deposit_account);
+      if (admin.adminAuth.equals(new_admin_auth.publicKey)) {
+        await program.methods.proposeAdmin(admin_auth.publicKey)
+          .accounts({
+            adminDepositAccount: admin_deposit_account,
+            adminAuth: new_admin_auth.publicKey,
+          }).signers([new_admin_auth]).rpc();
+
+        await program.methods.acceptAdmin()
+          .accounts({
+            adminDepositAccount: admin_deposit_account,
+            pendingAdminAuth: admin_auth.publicKey,
+          }).signers([admin_auth]).rpc();
+      } else if (admin.pendingAdminAuth !== null) {
+        await program.methods.cancelAdminProposal()
+          .accounts({
+            adminDepositAccount: admin_deposit_account,
+            adminAuth: admin_auth.publicKey,
+          }).signers([admin_auth]).rpc();
+      }
+    }
+
+    let result = await program.account.depositBaseAdmin.fetch(admin_deposit_account);
+    assert(result.adminAuth.equals(admin_auth.publicKey));
+    assert.equal(result.pendingAdminAuth, null);
+  });
+
 });